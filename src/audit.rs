@@ -0,0 +1,303 @@
+//! Security audit of archives before extracting them, see `zcatr audit`.
+
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    collect_archive_info, expand_globs, has_parent_component, is_absolute_path, plural, symbol,
+    Context, EntryInfo, Input, InputStats, ZcatError, EXPANSION_RATIO_THRESHOLD, S_IFDIR, S_IFLNK,
+    S_IFMT,
+};
+
+/// Arguments of the `audit` subcommand.
+#[derive(clap::Args, Debug)]
+pub(crate) struct AuditArgs {
+    #[arg(required = true, value_name = "FILES", help = "Archives to audit")]
+    files: Vec<PathBuf>,
+}
+
+/// How dangerous a finding is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Severity {
+    /// Worth knowing but harmless on its own
+    Info,
+    /// Needs a closer look before extracting the archive
+    Warning,
+    /// Extracting the archive would harm the system
+    Critical,
+}
+
+impl fmt::Display for Severity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let label = match self {
+            Severity::Info => "INFO",
+            Severity::Warning => "WARNING",
+            Severity::Critical => "CRITICAL",
+        };
+        // Padded so that the entries of a report are aligned
+        f.pad(label)
+    }
+}
+
+/// A problem found in an archive.
+#[derive(Debug)]
+struct Finding {
+    severity: Severity,
+    /// The entry the problem was found in, or `None` for the archive as a whole
+    entry: Option<String>,
+    description: String,
+}
+
+impl Finding {
+    fn new(severity: Severity, entry: &EntryInfo, description: impl Into<String>) -> Self {
+        Finding {
+            severity,
+            entry: Some(entry.name.clone()),
+            description: description.into(),
+        }
+    }
+}
+
+/// Tells whether a link would point outside of the directory the archive is extracted in.
+///
+/// The targets of symbolic links are relative to the directory of the link, those of
+/// hard links to the root of the archive.
+///
+/// # Arguments
+/// * `link_name` - The path of the link in the archive
+/// * `target` - The target of the link
+/// * `symbolic` - Whether the link is a symbolic link
+fn link_escapes(link_name: &str, target: &str, symbolic: bool) -> bool {
+    if is_absolute_path(target) {
+        return true;
+    }
+
+    let mut depth: i64 = match symbolic {
+        true => {
+            link_name
+                .trim_end_matches('/')
+                .split('/')
+                .filter(|component| !component.is_empty() && *component != ".")
+                .count() as i64
+                - 1
+        }
+        false => 0,
+    };
+    for component in target.split(['/', '\\']) {
+        match component {
+            "" | "." => {}
+            ".." => depth -= 1,
+            _ => depth += 1,
+        }
+        if depth < 0 {
+            return true;
+        }
+    }
+    false
+}
+
+/// Runs the checks of the audit on the entries of an archive.
+///
+/// # Arguments
+/// * `context` - The options of the command line
+/// * `entries` - The entries of the archive, in archive order
+/// * `archive_size` - The size of the archive file, unknown for the standard input
+///
+/// # Returns
+/// The findings, the most severe first
+fn audit_entries(
+    context: &Context,
+    entries: &[EntryInfo],
+    archive_size: Option<u64>,
+) -> Vec<Finding> {
+    let max_ratio = context.max_ratio;
+    let mut findings = Vec::new();
+    let mut names: HashMap<String, &str> = HashMap::new();
+
+    for entry in entries {
+        if is_absolute_path(&entry.name) {
+            findings.push(Finding::new(Severity::Critical, entry, "absolute path"));
+        }
+        if has_parent_component(&entry.name) {
+            findings.push(Finding::new(
+                Severity::Critical,
+                entry,
+                "path traversal (`..`)",
+            ));
+        }
+        if let Some(other) = names.insert(entry.name.to_lowercase(), &entry.name) {
+            findings.push(Finding::new(
+                Severity::Warning,
+                entry,
+                format!("collides with {other:?} on case-insensitive file systems"),
+            ));
+        }
+
+        let file_type = entry.mode.map(|mode| mode & S_IFMT);
+        if let Some(target) = &entry.link_target {
+            let symbolic = file_type == Some(S_IFLNK);
+            if link_escapes(&entry.name, target, symbolic) {
+                let kind = if symbolic { "symbolic" } else { "hard" };
+                findings.push(Finding::new(
+                    Severity::Critical,
+                    entry,
+                    format!("{kind} link to {target:?}, outside of the archive"),
+                ));
+            }
+        }
+        if let Some(mode) = entry.mode.filter(|_| file_type != Some(S_IFDIR)) {
+            if mode & 0o4000 != 0 {
+                findings.push(Finding::new(Severity::Warning, entry, "setuid bit"));
+            }
+            if mode & 0o2000 != 0 {
+                findings.push(Finding::new(Severity::Warning, entry, "setgid bit"));
+            }
+        }
+
+        if let (Some(ratio), Some(compressed_size)) = (max_ratio, entry.compressed_size) {
+            if entry.size > EXPANSION_RATIO_THRESHOLD
+                && entry.size > compressed_size.saturating_mul(ratio)
+            {
+                findings.push(Finding::new(
+                    Severity::Critical,
+                    entry,
+                    format!(
+                        "expands {} times, this looks like a decompression bomb",
+                        entry.size / compressed_size.max(1)
+                    ),
+                ));
+            }
+        }
+        if let Some(encryption) = entry.encryption {
+            let severity = match entry.locked {
+                true => Severity::Warning,
+                false => Severity::Info,
+            };
+            let description = match entry.locked {
+                true => format!("encrypted ({encryption}), its content cannot be inspected"),
+                false => format!("encrypted ({encryption})"),
+            };
+            findings.push(Finding::new(severity, entry, description));
+        }
+    }
+
+    // Archives compressing all their entries at once (e.g. TAR+GZIP) have a single ratio
+    if let (Some(ratio), Some(archive_size)) = (max_ratio, archive_size) {
+        let total: u64 = entries.iter().map(|entry| entry.size).sum();
+        if entries.iter().all(|entry| entry.compressed_size.is_none())
+            && total > EXPANSION_RATIO_THRESHOLD
+            && total > archive_size.saturating_mul(ratio)
+        {
+            findings.push(Finding {
+                severity: Severity::Critical,
+                entry: None,
+                description: format!(
+                    "expands {} times, this looks like a decompression bomb",
+                    total / archive_size.max(1)
+                ),
+            });
+        }
+    }
+
+    findings.sort_by_key(|finding| std::cmp::Reverse(finding.severity));
+    findings
+}
+
+/// Audits an archive and prints the report of its findings.
+///
+/// # Arguments
+/// * `context` - The options of the command line
+/// * `file_path` - Path to the archive to audit
+///
+/// # Returns
+/// * `Ok(Severity)` - The severity of the worst finding, `Severity::Info` if there is none
+/// * `Err(ZcatError)` - If the archive could not be read
+///
+/// # Output Format
+/// ```text
+/// 🔍 Audit of "release.zip"
+/// CRITICAL  ../../etc/cron.d/job: path traversal (`..`)
+/// WARNING   bin/helper: setuid bit
+/// 2 findings: 1 critical, 1 warning, 0 info
+/// ```
+fn audit_file(context: &Context, file_path: &Path) -> Result<Severity, ZcatError> {
+    let _span = tracing::info_span!("input", file = %file_path.display()).entered();
+    let mut input = Input::open(context, file_path)?;
+    let file_type = input.file_type(context, file_path)?;
+    let archive_size = input.len()?;
+
+    let findings = match collect_archive_info(context, file_path, input, file_type) {
+        Ok(info) => audit_entries(context, &info.entries, archive_size),
+        // The decompression stopped as soon as it exceeded the limits
+        Err(err) if err.is_limit_exceeded() => {
+            vec![Finding {
+                severity: Severity::Critical,
+                entry: None,
+                description: err.to_string(),
+            }]
+        }
+        Err(err) => return Err(err),
+    };
+
+    outln!("{}Audit of {file_path:?}", symbol(context, "🔍 ", ""));
+    for finding in &findings {
+        match &finding.entry {
+            Some(entry) => outln!("{:<9} {entry}: {}", finding.severity, finding.description),
+            None => outln!("{:<9} {}", finding.severity, finding.description),
+        }
+    }
+    let count = |severity| {
+        findings
+            .iter()
+            .filter(|finding| finding.severity == severity)
+            .count()
+    };
+    outln!(
+        "{}: {} critical, {} warning, {} info",
+        plural(findings.len(), "finding"),
+        count(Severity::Critical),
+        count(Severity::Warning),
+        count(Severity::Info)
+    );
+
+    Ok(findings
+        .iter()
+        .map(|finding| finding.severity)
+        .max()
+        .unwrap_or(Severity::Info))
+}
+
+/// Runs the `audit` subcommand.
+///
+/// # Arguments
+/// * `context` - The options of the command line
+/// * `args` - The arguments of the subcommand
+///
+/// # Returns
+/// The exit code: 0 if nothing critical was found, 1 if something critical was found
+/// and 3 if an archive could not be audited
+pub(crate) fn run(context: &Context, args: &AuditArgs) -> i32 {
+    let mut exit_code = 0;
+    let files = expand_globs(&args.files);
+    let run_stats = InputStats::start();
+    for (i, file_path) in files.iter().enumerate() {
+        if i > 0 {
+            outln!();
+        }
+        let stats = InputStats::start();
+        match audit_file(context, file_path) {
+            Ok(Severity::Critical) => exit_code = exit_code.max(1),
+            Ok(_) => {}
+            Err(err) => {
+                eprintln!("An error occurred while auditing the file: {file_path:?}. Error: {err}");
+                exit_code = 3;
+            }
+        }
+        stats.report(context, &format!("{file_path:?}"));
+    }
+    run_stats.report_total(context, files.len());
+    exit_code
+}
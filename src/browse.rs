@@ -0,0 +1,801 @@
+//! Interactive browser of archives in the terminal, see `zcatr browse`.
+//!
+//! The files of the archive are listed as a tree on the left, the beginning of the selected one
+//! is previewed on the right. Archives nested in the archive can be browsed in turn, the archive
+//! being browsed is then kept in memory.
+
+use std::{
+    cell::RefCell,
+    collections::HashSet,
+    fs::{self, File},
+    io::{self, IsTerminal, Read, Seek, Write},
+    path::{Path, PathBuf},
+};
+
+use ratatui::{
+    crossterm::event::{self, Event, KeyCode, KeyEventKind},
+    layout::{Constraint, Layout},
+    style::{Modifier, Style},
+    symbols::border,
+    text::Line,
+    widgets::{Block, List, ListItem, ListState, Paragraph},
+    DefaultTerminal, Frame,
+};
+
+use crate::{
+    detect_file_type, format_file_size, handle_entries, input_name, is_previewed, output_path,
+    plural, read_to_end_limited, read_up_to, symbol, Context, Input, ZcatError,
+};
+
+/// Arguments of the `browse` subcommand.
+#[derive(clap::Args, Debug)]
+pub(crate) struct BrowseArgs {
+    #[arg(value_name = "ARCHIVE", help = "Archive to browse")]
+    archive: PathBuf,
+}
+
+/// How many bytes of every file are kept to preview it.
+const PREVIEW_SIZE: usize = 16 * 1024;
+
+/// How many lines the preview scrolls by, see `PageUp` and `PageDown`.
+const SCROLL_STEP: u16 = 10;
+
+/// The keybindings, displayed at the bottom of the screen.
+const HELP: &str = "↑↓ move  Enter open  ← back  / search  x extract  PgUp/PgDn scroll  q quit";
+
+/// The keybindings with `--ascii`.
+const ASCII_HELP: &str =
+    "Up/Down move  Enter open  Left back  / search  x extract  PgUp/PgDn scroll  q quit";
+
+/// The borders of the panes with `--ascii`.
+const ASCII_BORDER: border::Set = border::Set {
+    top_left: "+",
+    top_right: "+",
+    bottom_left: "+",
+    bottom_right: "+",
+    vertical_left: "|",
+    vertical_right: "|",
+    horizontal_top: "-",
+    horizontal_bottom: "-",
+};
+
+/// A file of the archive being browsed.
+struct Entry {
+    name: String,
+    /// The size of the decompressed content
+    size: u64,
+    /// The first `PREVIEW_SIZE` bytes of the decompressed content
+    head: Vec<u8>,
+    mime_type: &'static str,
+}
+
+impl Entry {
+    /// Tells whether the file is an archive or a compressed file which can be browsed in turn.
+    fn is_archive(&self, context: &Context) -> bool {
+        context.registry.get(self.mime_type).is_some()
+    }
+}
+
+/// A line of the tree of files.
+enum Row {
+    Directory { path: String, depth: usize },
+    Entry { index: usize, depth: usize },
+}
+
+/// An archive being browsed, the one given on the command line or an archive nested in it.
+struct Level<'c> {
+    context: &'c Context,
+    /// The name displayed above the tree, `outer.zip!inner.tar.gz` for nested archives
+    label: String,
+    /// The path of the archive, naming the decompressed files of compressed files
+    path: PathBuf,
+    /// The archive, read again every time a file is opened or extracted: the one given on
+    /// the command line, or loaded in memory when it cannot be rewound, like the archives
+    /// nested in others
+    input: RefCell<Input>,
+    mime_type: &'static str,
+    entries: Vec<Entry>,
+    /// The directories whose files are hidden
+    collapsed: HashSet<String>,
+    /// The text typed after `/`, filtering the files by name
+    query: String,
+    rows: Vec<Row>,
+    state: ListState,
+}
+
+impl<'c> Level<'c> {
+    /// Reads the archive given on the command line.
+    ///
+    /// # Arguments
+    /// * `context` - The options of the command line
+    /// * `path` - Path to the archive
+    ///
+    /// # Returns
+    /// * `Ok(Level)` - The archive, with the beginning of all its files
+    /// * `Err(ZcatError)` - If the archive could not be read
+    fn open(context: &'c Context, path: &Path) -> Result<Level<'c>, ZcatError> {
+        let (input, mime_type) = Input::open_rewindable(context, path)?;
+        Level::read(
+            context,
+            path.display().to_string(),
+            path.to_owned(),
+            input,
+            mime_type,
+        )
+    }
+
+    /// Reads the name, the size and the beginning of every file of an archive.
+    ///
+    /// # Arguments
+    /// * `context` - The options of the command line
+    /// * `label` - The name displayed above the tree
+    /// * `path` - The path of the archive
+    /// * `input` - The archive, at its start
+    /// * `mime_type` - The MIME type detected for the archive
+    fn read(
+        context: &'c Context,
+        label: String,
+        path: PathBuf,
+        input: Input,
+        mime_type: &'static str,
+    ) -> Result<Level<'c>, ZcatError> {
+        let mut level = Level {
+            context,
+            label,
+            path,
+            input: RefCell::new(input),
+            mime_type,
+            entries: Vec::new(),
+            collapsed: HashSet::new(),
+            query: String::new(),
+            rows: Vec::new(),
+            state: ListState::default().with_selected(Some(0)),
+        };
+        let mut entries = Vec::new();
+        level.scan(|name, reader| {
+            let mut head = vec![0; PREVIEW_SIZE];
+            let read_bytes = read_up_to(reader, &mut head)?;
+            head.truncate(read_bytes);
+            let size = read_bytes as u64 + io::copy(reader, &mut io::sink())?;
+            entries.push(Entry {
+                name: level.entry_name(name),
+                size,
+                mime_type: detect_file_type(context, name, &head),
+                head,
+            });
+            Ok(())
+        })?;
+        level.entries = entries;
+        level.refresh_rows();
+        Ok(level)
+    }
+
+    /// Applies a handler function to every file of the archive, see `handle_entries`.
+    fn scan<F>(&self, handler: F) -> Result<(), ZcatError>
+    where
+        F: FnMut(Option<&str>, &mut dyn Read) -> Result<(), ZcatError>,
+    {
+        let mut input = self.input.borrow_mut();
+        input.rewind()?;
+        handle_entries(
+            self.context,
+            &self.path,
+            &mut *input,
+            self.mime_type,
+            handler,
+        )
+    }
+
+    /// Returns the name of a file, inputs which are not archives being named after themselves.
+    fn entry_name(&self, name: Option<&str>) -> String {
+        match name {
+            Some(name) => name.to_owned(),
+            None => self.path.file_name().map_or_else(
+                || self.label.clone(),
+                |name| name.to_string_lossy().into_owned(),
+            ),
+        }
+    }
+
+    /// Reads the whole decompressed content of a file, within the `--max-memory` limit.
+    ///
+    /// # Arguments
+    /// * `name` - The name of the file, the first one being read when several have it
+    fn read_entry(&self, name: &str) -> Result<Vec<u8>, ZcatError> {
+        let mut input = self.input.borrow_mut();
+        input.rewind()?;
+        let mut content = Vec::new();
+        let found = self.context.registry.open_entry(
+            &input_name(&self.path),
+            &mut *input,
+            self.mime_type,
+            self.context,
+            name,
+            |reader| {
+                read_to_end_limited(self.context, reader, &mut content)?;
+                Ok::<_, ZcatError>(())
+            },
+        )?;
+        match found {
+            true => Ok(content),
+            false => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "the file is no longer in the archive",
+            )
+            .into()),
+        }
+    }
+
+    /// Lays the files out as a tree, or as a flat list of the files matching the search.
+    ///
+    /// Every directory has a row, even when the archive holds no entry for it.
+    fn refresh_rows(&mut self) {
+        let mut order: Vec<usize> = (0..self.entries.len()).collect();
+        order.sort_by(|&a, &b| {
+            let components = |index: usize| self.entries[index].name.split('/');
+            components(a).cmp(components(b))
+        });
+
+        let mut rows = Vec::new();
+        if !self.query.is_empty() {
+            let query = self.query.to_lowercase();
+            rows.extend(
+                order
+                    .into_iter()
+                    .filter(|&index| self.entries[index].name.to_lowercase().contains(&query))
+                    .map(|index| Row::Entry { index, depth: 0 }),
+            );
+        } else {
+            // The directories of the previous file
+            let mut opened: Vec<&str> = Vec::new();
+            for index in order {
+                let components: Vec<&str> = self.entries[index]
+                    .name
+                    .split('/')
+                    .filter(|component| !component.is_empty())
+                    .collect();
+                let Some((_, dirs)) = components.split_last() else {
+                    continue;
+                };
+                let common = opened
+                    .iter()
+                    .zip(dirs)
+                    .take_while(|(opened, dir)| opened == dir)
+                    .count();
+                opened.truncate(common);
+                let collapsed_at = (0..dirs.len())
+                    .find(|&depth| self.collapsed.contains(&dirs[..=depth].join("/")));
+                for depth in common..dirs.len() {
+                    opened.push(dirs[depth]);
+                    if collapsed_at.is_none_or(|collapsed_at| depth <= collapsed_at) {
+                        rows.push(Row::Directory {
+                            path: dirs[..=depth].join("/"),
+                            depth,
+                        });
+                    }
+                }
+                if collapsed_at.is_none() {
+                    rows.push(Row::Entry {
+                        index,
+                        depth: dirs.len(),
+                    });
+                }
+            }
+        }
+
+        self.rows = rows;
+        let selected = self.state.selected().unwrap_or(0);
+        self.state
+            .select(Some(selected.min(self.rows.len().saturating_sub(1))));
+    }
+
+    fn selected_row(&self) -> Option<&Row> {
+        self.state.selected().and_then(|index| self.rows.get(index))
+    }
+
+    fn selected_entry(&self) -> Option<&Entry> {
+        match self.selected_row()? {
+            Row::Entry { index, .. } => Some(&self.entries[*index]),
+            Row::Directory { .. } => None,
+        }
+    }
+
+    /// Returns the text of a row of the tree.
+    fn row_label(&self, row: &Row) -> String {
+        match row {
+            Row::Directory { path, depth } => {
+                let marker = match self.collapsed.contains(path) {
+                    true => symbol(self.context, "▸", "+"),
+                    false => symbol(self.context, "▾", "-"),
+                };
+                let name = path.rsplit('/').next().unwrap_or(path);
+                format!("{}{marker} {name}/", "  ".repeat(*depth))
+            }
+            Row::Entry { index, depth } => {
+                let entry = &self.entries[*index];
+                // The search lists the files with their whole path
+                let name = match self.query.is_empty() {
+                    true => entry.name.trim_end_matches('/').rsplit('/').next().unwrap(),
+                    false => &entry.name,
+                };
+                let marker = match entry.is_archive(self.context) {
+                    true => '+',
+                    false => ' ',
+                };
+                format!(
+                    "{}{marker} {name}  {}",
+                    "  ".repeat(*depth),
+                    format_file_size(self.context, entry.size)
+                )
+            }
+        }
+    }
+
+    /// Returns the title and the text of the preview of the selected row.
+    fn preview(&self) -> (String, String) {
+        match self.selected_row() {
+            None => (String::new(), "The archive is empty.".to_owned()),
+            Some(Row::Directory { path, .. }) => {
+                let prefix = format!("{path}/");
+                let files = self.entries.iter().filter(|entry| {
+                    entry
+                        .name
+                        .trim_start_matches('/')
+                        .starts_with(prefix.as_str())
+                });
+                let (count, size) = files.fold((0, 0), |(count, size), entry| {
+                    (count + 1, size + entry.size)
+                });
+                (
+                    prefix,
+                    format!(
+                        "{}, {}",
+                        plural(count, "file"),
+                        format_file_size(self.context, size)
+                    ),
+                )
+            }
+            Some(Row::Entry { index, .. }) => {
+                let entry = &self.entries[*index];
+                let title = format!(
+                    "{} ({}, {})",
+                    entry.name,
+                    entry.mime_type,
+                    format_file_size(self.context, entry.size)
+                );
+                let is_text = entry.mime_type.starts_with("text/")
+                    || matches!(
+                        entry.mime_type,
+                        "application/json" | "application/xml" | "application/x-empty"
+                    )
+                    || is_previewed(self.context, entry.mime_type);
+                let text = if entry.is_archive(self.context) {
+                    "Archive, press Enter to browse its files.".to_owned()
+                } else if is_text {
+                    let mut text = printable(self.context, &entry.head);
+                    if entry.size > entry.head.len() as u64 {
+                        text.push_str(&format!(
+                            "\n{} only the first {} are previewed",
+                            symbol(self.context, "…", "..."),
+                            format_file_size(self.context, PREVIEW_SIZE as u64)
+                        ));
+                    }
+                    text
+                } else {
+                    "Preview not available, the content is binary.".to_owned()
+                };
+                (title, text)
+            }
+        }
+    }
+}
+
+/// Turns the beginning of a file into text which can be drawn on the screen, without the
+/// control characters which would move the cursor or change the colors.
+fn printable(context: &Context, head: &[u8]) -> String {
+    String::from_utf8_lossy(head)
+        .chars()
+        .filter_map(|char| match char {
+            '\t' => Some(' '),
+            '\r' => None,
+            char if char.is_control() && char != '\n' => match context.ascii {
+                true => Some('?'),
+                false => Some('�'),
+            },
+            char => Some(char),
+        })
+        .collect()
+}
+
+/// Writes the content of an extracted file, never overwriting an existing one.
+fn write_new_file(path: &Path, content: &[u8]) -> Result<(), ZcatError> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    File::create_new(path)?.write_all(content)?;
+    Ok(())
+}
+
+/// The state of the browser.
+struct Browser<'c> {
+    /// The archive given on the command line, followed by the nested archives being browsed
+    levels: Vec<Level<'c>>,
+    /// Whether the keys typed are added to the search
+    searching: bool,
+    /// How many lines of the preview are scrolled past
+    scroll: u16,
+    /// The outcome of the last action, displayed instead of the keybindings
+    status: Option<String>,
+}
+
+impl<'c> Browser<'c> {
+    fn level(&self) -> &Level<'c> {
+        self.levels.last().unwrap()
+    }
+
+    fn level_mut(&mut self) -> &mut Level<'c> {
+        self.levels.last_mut().unwrap()
+    }
+
+    fn run(&mut self, terminal: &mut DefaultTerminal) -> io::Result<()> {
+        loop {
+            terminal.draw(|frame| self.draw(frame))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+
+            if self.searching {
+                match key.code {
+                    KeyCode::Enter => self.searching = false,
+                    KeyCode::Esc => {
+                        self.searching = false;
+                        self.search(String::new());
+                    }
+                    KeyCode::Backspace => {
+                        let mut query = self.level().query.clone();
+                        query.pop();
+                        self.search(query);
+                    }
+                    KeyCode::Char(char) => {
+                        let query = format!("{}{char}", self.level().query);
+                        self.search(query);
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+
+            self.status = None;
+            match key.code {
+                KeyCode::Char('q') => return Ok(()),
+                KeyCode::Esc if !self.level().query.is_empty() => self.search(String::new()),
+                KeyCode::Esc if self.levels.len() == 1 => return Ok(()),
+                KeyCode::Esc | KeyCode::Left | KeyCode::Backspace | KeyCode::Char('h')
+                    if self.levels.len() > 1 =>
+                {
+                    self.levels.pop();
+                    self.scroll = 0;
+                }
+                KeyCode::Up | KeyCode::Char('k') => self.select(ListState::select_previous),
+                KeyCode::Down | KeyCode::Char('j') => self.select(ListState::select_next),
+                KeyCode::Home | KeyCode::Char('g') => self.select(ListState::select_first),
+                KeyCode::End | KeyCode::Char('G') => self.select(ListState::select_last),
+                KeyCode::PageDown => self.scroll = self.scroll.saturating_add(SCROLL_STEP),
+                KeyCode::PageUp => self.scroll = self.scroll.saturating_sub(SCROLL_STEP),
+                KeyCode::Enter | KeyCode::Right | KeyCode::Char('l') => self.open(),
+                KeyCode::Char('/') => self.searching = true,
+                KeyCode::Char('x') => self.extract(),
+                _ => {}
+            }
+        }
+    }
+
+    fn select(&mut self, select: fn(&mut ListState)) {
+        select(&mut self.level_mut().state);
+        self.scroll = 0;
+    }
+
+    fn search(&mut self, query: String) {
+        let level = self.level_mut();
+        level.query = query;
+        level.state.select_first();
+        level.refresh_rows();
+        self.scroll = 0;
+    }
+
+    /// Collapses or expands the selected directory, or browses the selected archive.
+    fn open(&mut self) {
+        let level = self.level();
+        match level.selected_row() {
+            Some(Row::Directory { path, .. }) => {
+                let path = path.clone();
+                let level = self.level_mut();
+                if !level.collapsed.remove(&path) {
+                    level.collapsed.insert(path);
+                }
+                level.refresh_rows();
+            }
+            Some(Row::Entry { index, .. }) => {
+                let entry = &level.entries[*index];
+                if !entry.is_archive(level.context) {
+                    return;
+                }
+                let nested = level.read_entry(&entry.name).and_then(|content| {
+                    Level::read(
+                        level.context,
+                        format!("{}!{}", level.label, entry.name),
+                        PathBuf::from(&entry.name),
+                        Input::Memory(io::Cursor::new(content)),
+                        entry.mime_type,
+                    )
+                });
+                match nested {
+                    Ok(nested) => {
+                        self.levels.push(nested);
+                        self.scroll = 0;
+                    }
+                    Err(err) => {
+                        self.status = Some(format!("Could not browse the archive. Error: {err}"))
+                    }
+                }
+            }
+            None => {}
+        }
+    }
+
+    /// Writes the selected file in the current directory, under its path in the archive.
+    fn extract(&mut self) {
+        let level = self.level();
+        let Some(entry) = level.selected_entry() else {
+            return;
+        };
+        let status = match output_path(Path::new("."), &entry.name) {
+            Some(path) => match level
+                .read_entry(&entry.name)
+                .and_then(|content| write_new_file(&path, &content))
+            {
+                Ok(()) => format!("Extracted to {}", path.display()),
+                Err(err) => format!("Could not extract {:?}. Error: {err}", entry.name),
+            },
+            None => format!("Not extracted, the path is unsafe: {:?}", entry.name),
+        };
+        self.status = Some(status);
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, bottom] =
+            Layout::vertical([Constraint::Fill(1), Constraint::Length(1)]).areas(frame.area());
+        let [tree, preview] =
+            Layout::horizontal([Constraint::Percentage(40), Constraint::Fill(1)]).areas(main);
+
+        let context = self.level().context;
+        let bottom_line = match (&self.status, self.searching) {
+            (_, true) => format!("/{}", self.level().query),
+            (Some(status), false) => status.clone(),
+            (None, false) => symbol(context, HELP, ASCII_HELP).to_owned(),
+        };
+        let scroll = self.scroll;
+        let level = self.level_mut();
+        let items: Vec<ListItem> = level
+            .rows
+            .iter()
+            .map(|row| ListItem::new(level.row_label(row)))
+            .collect();
+        let title = match level.query.is_empty() {
+            true => format!(" {} ", level.label),
+            false => format!(" {} /{} ", level.label, level.query),
+        };
+        let border_set = match context.ascii {
+            true => ASCII_BORDER,
+            false => border::PLAIN,
+        };
+        let list = List::new(items)
+            .block(Block::bordered().border_set(border_set).title(title))
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, tree, &mut level.state);
+
+        let (title, text) = level.preview();
+        let paragraph = Paragraph::new(text)
+            .block(
+                Block::bordered()
+                    .border_set(border_set)
+                    .title(format!(" {title} ")),
+            )
+            .scroll((scroll, 0));
+        frame.render_widget(paragraph, preview);
+        frame.render_widget(Line::from(bottom_line), bottom);
+    }
+}
+
+/// Runs the `browse` subcommand.
+///
+/// # Arguments
+/// * `context` - The options of the command line
+/// * `args` - The arguments of the subcommand
+///
+/// # Returns
+/// The exit code: 0 once the browser is quit, 2 outside of a terminal, the exit status of
+/// the failure (see `ExitStatus`) if the archive could not be read
+pub(crate) fn run(context: &Context, args: &BrowseArgs) -> i32 {
+    if !io::stdout().is_terminal() {
+        eprintln!("zcatr browse needs a terminal, use zcatr list and zcatr extract instead");
+        return 2;
+    }
+    let level = match Level::open(context, &args.archive) {
+        Ok(level) => level,
+        Err(err) => {
+            eprintln!(
+                "An error occurred while processing the file: {:?}. Error: {}",
+                args.archive, err
+            );
+            return err.exit_status() as i32;
+        }
+    };
+
+    let mut browser = Browser {
+        levels: vec![level],
+        searching: false,
+        scroll: 0,
+        status: None,
+    };
+    let mut terminal = ratatui::init();
+    let result = browser.run(&mut terminal);
+    ratatui::restore();
+    match result {
+        Ok(()) => 0,
+        Err(err) => {
+            eprintln!("The terminal could not be used. Error: {err}");
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Reads a TAR archive holding files, in memory.
+    fn read_tar<'c>(context: &'c Context, files: &[(&str, &[u8])]) -> Level<'c> {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in files {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder.append_data(&mut header, name, *content).unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+        let input = Input::Memory(io::Cursor::new(tar));
+        let path = PathBuf::from("test.tar");
+        Level::read(
+            context,
+            "test.tar".to_owned(),
+            path,
+            input,
+            "application/x-tar",
+        )
+        .unwrap()
+    }
+
+    fn labels(level: &Level) -> Vec<String> {
+        level.rows.iter().map(|row| level.row_label(row)).collect()
+    }
+
+    #[test]
+    fn test_refresh_rows() {
+        let context = Context::default();
+        let files: &[(&str, &[u8])] = &[
+            ("b/2.txt", b"two"),
+            ("a.txt", b"a"),
+            ("b/1.txt", b"one"),
+            ("b/c/3.txt", b"three"),
+        ];
+        let mut level = read_tar(&context, files);
+        assert_eq!(
+            labels(&level),
+            [
+                "  a.txt  1 Bytes",
+                "▾ b/",
+                "    1.txt  3 Bytes",
+                "    2.txt  3 Bytes",
+                "  ▾ c/",
+                "      3.txt  5 Bytes",
+            ]
+        );
+
+        level.collapsed.insert("b/c".to_owned());
+        level.refresh_rows();
+        assert_eq!(labels(&level)[4..], ["  ▸ c/"]);
+        level.collapsed.insert("b".to_owned());
+        level.refresh_rows();
+        assert_eq!(labels(&level), ["  a.txt  1 Bytes", "▸ b/"]);
+
+        // The search lists the matching files with their whole path
+        level.query = "C/3".to_owned();
+        level.state.select(Some(5));
+        level.refresh_rows();
+        assert_eq!(labels(&level), ["  b/c/3.txt  5 Bytes"]);
+        assert_eq!(level.state.selected(), Some(0));
+    }
+
+    #[test]
+    fn test_row_label() {
+        let context = Context {
+            ascii: true,
+            ..Context::default()
+        };
+        let mut level = read_tar(&context, &[("logs/app.log.gz", b"\x1f\x8b\x08\0")]);
+        level.collapsed.insert("logs".to_owned());
+        level.refresh_rows();
+        assert_eq!(labels(&level), ["+ logs/"]);
+        level.collapsed.clear();
+        level.refresh_rows();
+        // Archives are marked, as they can be browsed in turn
+        assert_eq!(labels(&level), ["- logs/", "  + app.log.gz  4 Bytes"]);
+    }
+
+    #[test]
+    fn test_preview() {
+        let context = Context::default();
+        let large = "line\n".repeat(PREVIEW_SIZE);
+        let files: &[(&str, &[u8])] = &[
+            ("docs/notes.txt", b"Hello\tWorld\r\n"),
+            ("docs/large.txt", large.as_bytes()),
+            ("image.bin", b"\0\x01\x02\x03\xff"),
+        ];
+        let mut level = read_tar(&context, files);
+
+        let preview = |level: &mut Level, row| {
+            level.state.select(Some(row));
+            level.preview()
+        };
+        assert_eq!(
+            preview(&mut level, 0),
+            ("docs/".to_owned(), "2 files, 80.01 KB".to_owned())
+        );
+        let (title, text) = preview(&mut level, 1);
+        assert_eq!(title, "docs/large.txt (text/plain, 80.00 KB)");
+        assert!(text.ends_with("\n… only the first 16.00 KB are previewed"));
+        assert_eq!(
+            preview(&mut level, 2),
+            (
+                "docs/notes.txt (text/plain, 13 Bytes)".to_owned(),
+                "Hello World\n".to_owned()
+            )
+        );
+        assert_eq!(
+            preview(&mut level, 3).1,
+            "Preview not available, the content is binary."
+        );
+
+        let empty = read_tar(&context, &[]);
+        assert_eq!(
+            empty.preview(),
+            (String::new(), "The archive is empty.".to_owned())
+        );
+    }
+
+    #[test]
+    fn test_printable() {
+        let text = b"a\tb\r\nc\x1b[31md\x07";
+        assert_eq!(printable(&Context::default(), text), "a b\nc�[31md�");
+        let context = Context {
+            ascii: true,
+            ..Context::default()
+        };
+        assert_eq!(printable(&context, text), "a b\nc?[31md?");
+        assert_eq!(printable(&context, b"caf\xc3"), "caf\u{fffd}");
+    }
+
+    #[test]
+    fn test_read_entry() {
+        let context = Context::default();
+        let level = read_tar(&context, &[("a.txt", b"alpha"), ("b.txt", b"beta")]);
+        assert_eq!(level.read_entry("b.txt").unwrap(), b"beta");
+        // The archive is rewound every time
+        assert_eq!(level.read_entry("a.txt").unwrap(), b"alpha");
+        assert!(level.read_entry("c.txt").is_err());
+    }
+}
@@ -0,0 +1,391 @@
+//! The configuration file, holding the defaults of the command line options.
+//!
+//! It is read from `$XDG_CONFIG_HOME/zcatr/config.toml` (`~/.config/zcatr/config.toml` when
+//! `XDG_CONFIG_HOME` is not set), or `%APPDATA%\zcatr\config.toml` on Windows. The options
+//! given on the command line always override its settings.
+//!
+//! Options can also be given in the `ZCATR_OPTS` environment variable, like `GZIP` or `LESS`,
+//! to be added before those of the command line (see `with_env_options`).
+
+use std::{
+    env,
+    ffi::OsString,
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+use clap::ValueEnum;
+use glob::Pattern;
+use serde::{Deserialize, Deserializer};
+
+use crate::{
+    parse_glob, parse_header_format, parse_hex_bytes, parse_separator, ColorChoice, HeaderStyle,
+    SizeUnits,
+};
+
+/// The settings of the configuration file, all optional.
+///
+/// The values given to command line options are kept as strings, checked when the file is
+/// loaded, and handed over to clap as the default values of the options (see `apply`).
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub(crate) struct Config {
+    /// Whether to display the header and footer, see `--styling` and `--no-styling`
+    pub(crate) styling: Option<bool>,
+    style: Option<String>,
+    header_format: Option<String>,
+    footer_format: Option<String>,
+    separator: Option<String>,
+    color: Option<String>,
+    /// Whether to page the output, or the command of the pager to use
+    pub(crate) pager: Option<PagerSetting>,
+    /// Powers of 1024 (binary) or of 1000 (decimal) to format sizes with
+    pub(crate) size_units: SizeUnits,
+    include: Vec<String>,
+    exclude: Vec<String>,
+    /// MIME types whose content is displayed like text, `type/*` matching a whole type
+    pub(crate) preview: Vec<String>,
+    /// The external commands decompressing the inputs whose format zcatr does not read
+    pub(crate) decompressor: Vec<Decompressor>,
+    /// The in-house formats read by external commands, see `plugin`
+    pub(crate) format: Vec<FormatPlugin>,
+}
+
+/// A `[[decompressor]]` of the configuration file: an external command decompressing the
+/// inputs whose format zcatr does not read, like `LESSOPEN` does for less.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(try_from = "DecompressorSetting")]
+pub(crate) struct Decompressor {
+    /// Matched against the name of the inputs, or their whole path when it has a slash (see
+    /// `matches_globs`)
+    pub(crate) glob: Pattern,
+    /// The program and its arguments, in which `{path}` stands for the path of the input
+    pub(crate) command: Vec<String>,
+}
+
+/// A `[[decompressor]]` as written in the configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct DecompressorSetting {
+    glob: String,
+    command: String,
+}
+
+impl TryFrom<DecompressorSetting> for Decompressor {
+    type Error = String;
+
+    /// Parses the glob and splits the command like a shell does, the path of the input being
+    /// added at the end of the command when it has no `{path}`.
+    fn try_from(setting: DecompressorSetting) -> Result<Self, Self::Error> {
+        let glob = parse_glob(&setting.glob).map_err(|err| format!("glob: {err}"))?;
+        let mut command =
+            shell_words::split(&setting.command).map_err(|err| format!("command: {err}"))?;
+        if command.is_empty() {
+            return Err("command: empty command".to_owned());
+        }
+        if !command.iter().any(|arg| arg.contains("{path}")) {
+            command.push("{path}".to_owned());
+        }
+        Ok(Decompressor { glob, command })
+    }
+}
+
+/// A `[[format]]` of the configuration file: a format zcatr does not know, recognized by its
+/// first bytes or by its extension, whose content is decoded by an external command.
+#[derive(Debug, Clone)]
+pub(crate) struct FormatPlugin {
+    /// The name of the format, lowercase
+    pub(crate) name: String,
+    /// `application/x-` followed by the name of the format
+    pub(crate) mime_type: &'static str,
+    /// The bytes the content of the format starts with, after `offset` bytes
+    pub(crate) magic: Option<Vec<u8>>,
+    pub(crate) offset: usize,
+    /// The extensions of the files in the format, lowercase and without their dot
+    pub(crate) extensions: Vec<String>,
+    /// The program and its arguments, reading the content on its standard input and writing
+    /// the decoded file, or a TAR archive of the decoded files, on its standard output
+    pub(crate) command: Vec<String>,
+}
+
+/// A `[[format]]` as written in the configuration file.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct FormatSetting {
+    name: String,
+    magic: Option<String>,
+    #[serde(default)]
+    offset: usize,
+    #[serde(default)]
+    extensions: Vec<String>,
+    command: String,
+}
+
+impl TryFrom<FormatSetting> for FormatPlugin {
+    type Error = String;
+
+    /// Parses the magic bytes written in hexadecimal, e.g. `"41 43 4d 45"`, and splits the
+    /// command like a shell does.
+    fn try_from(setting: FormatSetting) -> Result<Self, Self::Error> {
+        let name = setting.name.to_ascii_lowercase();
+        if name.is_empty()
+            || !name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '.' | '+'))
+        {
+            return Err(format!("name: invalid format name {:?}", setting.name));
+        }
+        let magic = setting
+            .magic
+            .map(|magic| {
+                parse_hex_bytes(&magic).map_err(|err| format!("magic: invalid bytes, {err}"))
+            })
+            .transpose()?;
+        if magic.is_none() && setting.extensions.is_empty() {
+            return Err(format!(
+                "format {name}: either magic or extensions must be set"
+            ));
+        }
+        let extensions = setting
+            .extensions
+            .iter()
+            .map(|extension| extension.trim_start_matches('.').to_lowercase())
+            .collect();
+        let command =
+            shell_words::split(&setting.command).map_err(|err| format!("command: {err}"))?;
+        if command.is_empty() {
+            return Err("command: empty command".to_owned());
+        }
+        Ok(FormatPlugin {
+            // Loaded once, the MIME types of the formats live as long as zcatr
+            mime_type: String::leak(format!("application/x-{name}")),
+            name,
+            magic,
+            offset: setting.offset,
+            extensions,
+            command,
+        })
+    }
+}
+
+// Not derived, as the MIME type would be taken for a string borrowed from the file
+impl<'de> Deserialize<'de> for FormatPlugin {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        FormatSetting::deserialize(deserializer)?
+            .try_into()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// The `pager` setting: `false` disables the paging, a string sets the pager to use when
+/// `$PAGER` is not set.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum PagerSetting {
+    Enabled(bool),
+    Command(String),
+}
+
+/// Returns the path of the configuration file, if the directory holding it is known.
+pub(crate) fn path() -> Option<PathBuf> {
+    let dir = match cfg!(windows) {
+        true => env::var_os("APPDATA").map(PathBuf::from),
+        false => env::var_os("XDG_CONFIG_HOME")
+            .filter(|dir| Path::new(dir).is_absolute())
+            .map(PathBuf::from)
+            .or_else(|| env::var_os("HOME").map(|home| Path::new(&home).join(".config"))),
+    };
+    dir.map(|dir| dir.join("zcatr").join("config.toml"))
+}
+
+/// Loads the configuration file, if any.
+///
+/// # Arguments
+/// * `path` - The path of the configuration file, see `path`
+///
+/// # Returns
+/// * `Ok(Config)` - The settings of the file, all unset if it does not exist
+/// * `Err(String)` - Why the file could not be read, parsed or has invalid settings
+pub(crate) fn load(path: &Path) -> Result<Config, String> {
+    let content = match fs::read_to_string(path) {
+        Ok(content) => content,
+        Err(err) if err.kind() == io::ErrorKind::NotFound => return Ok(Config::default()),
+        Err(err) => return Err(err.to_string()),
+    };
+    let config: Config = toml::from_str(&content).map_err(|err| err.to_string())?;
+
+    if let Some(style) = &config.style {
+        HeaderStyle::from_str(style, false).map_err(|err| format!("style: {err}"))?;
+    }
+    if let Some(color) = &config.color {
+        ColorChoice::from_str(color, false).map_err(|err| format!("color: {err}"))?;
+    }
+    for (key, format) in [
+        ("header-format", &config.header_format),
+        ("footer-format", &config.footer_format),
+    ] {
+        if let Some(format) = format {
+            parse_header_format(format).map_err(|err| format!("{key}: {err}"))?;
+        }
+    }
+    if let Some(separator) = &config.separator {
+        parse_separator(separator).map_err(|err| format!("separator: {err}"))?;
+    }
+    for (key, globs) in [("include", &config.include), ("exclude", &config.exclude)] {
+        for glob in globs {
+            parse_glob(glob).map_err(|err| format!("{key}: {err}"))?;
+        }
+    }
+    Ok(config)
+}
+
+impl Config {
+    /// Sets the settings of the file as the default values of the options of a command and of
+    /// its subcommands, so that the options given on the command line override them.
+    ///
+    /// `include` and `exclude` only apply to the commands walking directories (see `-R`), the
+    /// `--include` of `zcatr grep` filtering the files of archives instead.
+    pub(crate) fn apply(&self, mut command: clap::Command) -> clap::Command {
+        let defaults = [
+            ("style", self.style.as_slice()),
+            ("header_format", self.header_format.as_slice()),
+            ("footer_format", self.footer_format.as_slice()),
+            ("separator", self.separator.as_slice()),
+            ("color", self.color.as_slice()),
+            ("include", &self.include),
+            ("exclude", &self.exclude),
+        ];
+        let walks_directories = command
+            .get_arguments()
+            .any(|arg| arg.get_id() == "recursive");
+        for (id, values) in defaults {
+            let applies = match id {
+                "include" | "exclude" => walks_directories,
+                _ => true,
+            };
+            if values.is_empty()
+                || !applies
+                || !command.get_arguments().any(|arg| arg.get_id() == id)
+            {
+                continue;
+            }
+            command = command.mut_arg(id, |arg| arg.default_values(values.iter().cloned()));
+        }
+
+        let subcommands: Vec<String> = command
+            .get_subcommands()
+            .map(|subcommand| subcommand.get_name().to_owned())
+            .collect();
+        for name in subcommands {
+            command = command.mut_subcommand(name, |subcommand| self.apply(subcommand));
+        }
+        command
+    }
+}
+
+/// Adds the options of the `ZCATR_OPTS` environment variable to the arguments of the command line.
+///
+/// The options are split like a shell does and added right after the program name, or after the
+/// subcommand if any: the options given on the command line come last and override them (see
+/// `args_override_self`), except the repeatable ones like `-v` which add up. Only the
+/// options the subcommand accepts are added to it, so that e.g. `--no-styling` does not make
+/// `zcatr list` fail.
+///
+/// # Arguments
+/// * `args` - The arguments of the command line, program name included
+/// * `command` - The command parsing them, built (see `clap::Command::build`)
+///
+/// # Returns
+/// * `Ok(Vec<OsString>)` - The arguments with the options of `ZCATR_OPTS`
+/// * `Err(String)` - Why the variable could not be split
+pub(crate) fn with_env_options(
+    mut args: Vec<OsString>,
+    command: &clap::Command,
+) -> Result<Vec<OsString>, String> {
+    let Some(value) = env::var_os("ZCATR_OPTS") else {
+        return Ok(args);
+    };
+    let value = value.to_str().ok_or("invalid UTF-8")?;
+    let options = shell_words::split(value).map_err(|err| err.to_string())?;
+
+    let subcommand = args
+        .get(1)
+        .and_then(|arg| arg.to_str())
+        .and_then(|name| command.find_subcommand(name));
+    let (position, options) = match subcommand {
+        Some(subcommand) => (2, accepted_options(subcommand, command, options)),
+        None => (1, options),
+    };
+    let position = position.min(args.len());
+    args.splice(position..position, options.into_iter().map(OsString::from));
+    Ok(args)
+}
+
+/// Keeps the options a subcommand accepts, with their values.
+///
+/// # Arguments
+/// * `subcommand` - The subcommand the options are given to
+/// * `command` - The parent command, telling which of the other options take a value
+/// * `options` - The options to filter
+fn accepted_options(
+    subcommand: &clap::Command,
+    command: &clap::Command,
+    options: Vec<String>,
+) -> Vec<String> {
+    let mut accepted = Vec::new();
+    let mut options = options.into_iter();
+    while let Some(option) = options.next() {
+        let value_follows = match find_option(subcommand, &option) {
+            Some(value_follows) => {
+                accepted.push(option);
+                if let Some(value) = options.next().filter(|_| value_follows) {
+                    accepted.push(value);
+                }
+                continue;
+            }
+            None => find_option(command, &option).unwrap_or(false),
+        };
+        if value_follows {
+            options.next();
+        }
+    }
+    accepted
+}
+
+/// Tells whether an option is accepted by a command.
+///
+/// Only the first letter of a group of short options (e.g. `-nv`) is looked up.
+///
+/// # Arguments
+/// * `command` - The command to look the option up in
+/// * `option` - The option, e.g. `--color=always`, `--color` or `-n`
+///
+/// # Returns
+/// * `Some(bool)` - Whether the value of the option is the next argument
+/// * `None` - If the command does not accept the option, or it is not an option
+fn find_option(command: &clap::Command, option: &str) -> Option<bool> {
+    let (arg, has_value) = match option.strip_prefix("--") {
+        Some(long) => {
+            let (name, has_value) = match long.split_once('=') {
+                Some((name, _)) => (name, true),
+                None => (long, false),
+            };
+            let arg = command.get_arguments().find(|arg| {
+                arg.get_long() == Some(name)
+                    || arg
+                        .get_all_aliases()
+                        .is_some_and(|aliases| aliases.contains(&name))
+            })?;
+            (arg, has_value)
+        }
+        None => {
+            let mut shorts = option.strip_prefix('-')?.chars();
+            let short = shorts.next()?;
+            let arg = command
+                .get_arguments()
+                .find(|arg| arg.get_short() == Some(short))?;
+            (arg, !shorts.as_str().is_empty())
+        }
+    };
+    Some(arg.get_action().takes_values() && !has_value)
+}
@@ -0,0 +1,150 @@
+//! Printing of the data appended to a GZIP file as it arrives, like `tail -F`, see `--follow`.
+//!
+//! Logs compressed on the fly grow by whole GZIP members appended one after the other (e.g.
+//! with logrotate's `copytruncate`). The file is decompressed member by member, remembering
+//! where the last complete member ends: a member still being written is decompressed again
+//! once more data arrives, skipping what was already printed.
+
+use std::{
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Seek, SeekFrom, Write},
+    path::Path,
+    thread,
+    time::Duration,
+};
+
+use flate2::bufread::GzDecoder;
+
+use crate::{output, ExitStatus, BUFFER_SIZE};
+
+/// How often the file is checked for new data.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// The first bytes of every GZIP member.
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Where the decompression of the file stands.
+#[derive(Debug, Default)]
+struct Position {
+    /// The offset of the first member not decompressed entirely
+    member_offset: u64,
+    /// The number of bytes of that member already printed
+    printed: u64,
+}
+
+/// Prints the decompressed content of a GZIP file, then the content of the members appended
+/// to it as they arrive, until zcatr is interrupted.
+///
+/// Like `tail -F`, the file is followed by name: when it is truncated or replaced, it is
+/// read again from its start, and it is waited for while it does not exist.
+///
+/// # Arguments
+/// * `file_path` - Path to the file
+pub(crate) fn run(file_path: &Path) -> ! {
+    if let Err(err) = File::open(file_path).and_then(|mut file| check_magic(&mut file)) {
+        eprintln!(
+            "An error occurred while processing the file: {:?}. Error: {}",
+            file_path, err
+        );
+        ExitStatus::Failure.exit();
+    }
+
+    let mut position = Position::default();
+    let mut followed = None;
+    loop {
+        // The file may be missing for a while, between its rotation and its recreation
+        if let Ok(metadata) = fs::metadata(file_path) {
+            let id = file_id(&metadata);
+            if followed.is_some() && followed != Some(id) {
+                eprintln!("The following file was replaced, following the new one: {file_path:?}");
+                position = Position::default();
+            } else if metadata.len() < position.member_offset {
+                eprintln!("The following file was truncated, reading it again: {file_path:?}");
+                position = Position::default();
+            }
+            followed = Some(id);
+
+            if metadata.len() > position.member_offset {
+                if let Err(err) = decompress_new_members(file_path, &mut position) {
+                    eprintln!(
+                        "An error occurred while processing the file: {:?}. Error: {}",
+                        file_path, err
+                    );
+                    ExitStatus::Corrupt.exit();
+                }
+            }
+        }
+        thread::sleep(POLL_INTERVAL);
+    }
+}
+
+/// Checks that a file is empty or starts like a GZIP file.
+fn check_magic(file: &mut File) -> io::Result<()> {
+    let mut magic = Vec::new();
+    file.take(GZIP_MAGIC.len() as u64).read_to_end(&mut magic)?;
+    match GZIP_MAGIC.starts_with(&magic) {
+        true => Ok(()),
+        false => Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "only GZIP files can be followed",
+        )),
+    }
+}
+
+/// Prints the decompressed content of the members of the file from the given position,
+/// leaving the position at the first member which is not complete yet.
+///
+/// # Errors
+/// If the file cannot be read or its content is not GZIP data
+fn decompress_new_members(file_path: &Path, position: &mut Position) -> io::Result<()> {
+    let mut file = File::open(file_path)?;
+    file.seek(SeekFrom::Start(position.member_offset))?;
+    let mut reader = BufReader::new(file);
+    let mut buffer = [0u8; BUFFER_SIZE];
+    while !reader.fill_buf()?.is_empty() {
+        let mut decoder = GzDecoder::new(&mut reader);
+        let mut decompressed = 0;
+        let complete = loop {
+            let read_bytes = match decoder.read(&mut buffer) {
+                Ok(0) => break true,
+                Ok(read_bytes) => read_bytes,
+                // The decoder fails at the end of the data when the rest of the member is
+                // still to be written, the member being corrupt otherwise
+                Err(err) => match decoder.get_mut().fill_buf()?.is_empty() {
+                    true => break false,
+                    false => return Err(err),
+                },
+            };
+            let start = decompressed;
+            decompressed += read_bytes as u64;
+            // Skips what was printed the last time the member was decompressed
+            if decompressed > position.printed {
+                let skipped = position.printed.saturating_sub(start) as usize;
+                output::stdout().write_all(&buffer[skipped..read_bytes])?;
+                position.printed = decompressed;
+            }
+        };
+        output::flush();
+        if !complete {
+            return Ok(());
+        }
+        position.member_offset = reader.stream_position()?;
+        position.printed = 0;
+    }
+    Ok(())
+}
+
+/// Identifies the file behind a path, to notice when it is replaced by another one (e.g.
+/// when a log is rotated by renaming it).
+#[cfg(unix)]
+fn file_id(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+
+    Some((metadata.dev(), metadata.ino()))
+}
+
+/// Files cannot be told apart elsewhere, only their truncation is noticed.
+#[cfg(not(unix))]
+fn file_id(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
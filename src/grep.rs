@@ -0,0 +1,807 @@
+//! Search of patterns through the files of archives and compressed files, see `zcatr grep`.
+
+use std::{
+    collections::VecDeque,
+    io::{self, BufRead, BufReader, Read, Seek, Write},
+    num::NonZeroUsize,
+    path::{Path, PathBuf},
+    thread,
+};
+
+use clap::ValueEnum;
+use glob::Pattern;
+use regex::bytes::{Regex, RegexBuilder};
+
+use crate::{
+    detect_file_type, expand_globs, for_each_in_order, handle_entries, matches_globs, output,
+    paint, parse_glob, parse_hex_bytes, parse_size, read_to_end_limited, read_up_to, Context,
+    ExpansionError, Input, InputStats, Size, ZcatError, BUFFER_SIZE, MAGIC_BYTES_SIZE,
+};
+
+/// Arguments of the `grep` subcommand.
+#[derive(clap::Args, Debug)]
+pub(crate) struct GrepArgs {
+    #[arg(
+        help = "Regular expression to search for",
+        long_help = "Regular expression to search for, using the syntax of the regex crate \
+        (similar to `grep -E`), or sequence of bytes in hexadecimal with --bytes."
+    )]
+    pattern: String,
+
+    #[arg(
+        long,
+        action,
+        conflicts_with_all = ["ignore_case", "fixed_strings", "word_regexp", "after_context", "before_context", "context"],
+        help = "Search for a sequence of bytes given in hexadecimal (e.g. 'DE AD BE EF')",
+        long_help = "Interpret the pattern as a sequence of bytes written in hexadecimal, \
+        optionally separated by spaces or colons (e.g. 'DE AD BE EF' or 'cafe:babe'), and \
+        search for it anywhere in the files, binary or not. Every occurrence is printed \
+        with its offset in the decompressed file, as `archive!entry:0x1f40`."
+    )]
+    bytes: bool,
+
+    #[arg(
+        short,
+        long,
+        action,
+        help = "Ignore the case of the pattern and of the lines"
+    )]
+    ignore_case: bool,
+
+    #[arg(
+        short = 'F',
+        long,
+        action,
+        help = "Search for the pattern as a fixed string instead of a regular expression"
+    )]
+    fixed_strings: bool,
+
+    #[arg(
+        short,
+        long,
+        action,
+        help = "Only match whole words",
+        long_help = "Only match the pattern when it forms a whole word, i.e. when it is \
+        surrounded by word boundaries (the start or end of the line, or non-word characters)."
+    )]
+    word_regexp: bool,
+
+    #[arg(
+        short = 'A',
+        long,
+        value_name = "NUM",
+        help = "Print NUM lines of trailing context after matching lines"
+    )]
+    after_context: Option<usize>,
+
+    #[arg(
+        short = 'B',
+        long,
+        value_name = "NUM",
+        help = "Print NUM lines of leading context before matching lines"
+    )]
+    before_context: Option<usize>,
+
+    #[arg(
+        short = 'C',
+        long,
+        value_name = "NUM",
+        help = "Print NUM lines of context around matching lines",
+        long_help = "Print NUM lines of context before and after matching lines, unless \
+        overridden by -A or -B. Context lines are printed as `archive!entry-line-text` and \
+        non-contiguous groups of lines are separated by `--`, like grep does."
+    )]
+    context: Option<usize>,
+
+    #[arg(
+        short = 'l',
+        long,
+        action,
+        conflicts_with = "count",
+        help = "Only print the files containing matches",
+        long_help = "Only print the label (`archive!entry`) of the files containing at least \
+        one match, once each. The search of a file stops at its first match."
+    )]
+    files_with_matches: bool,
+
+    #[arg(
+        short = 'c',
+        long,
+        action,
+        help = "Only print the number of matching lines of every file",
+        long_help = "Only print the number of matching lines of every file searched, as \
+        `archive!entry:count`, including the files without any match."
+    )]
+    count: bool,
+
+    #[arg(
+        long,
+        action,
+        help = "Also search the archives nested in the searched archives",
+        long_help = "Also search the files of the archives (ZIP, TAR, GZIP, BZIP2, XZ, ZSTD and the formats \
+        of the configuration file) found inside the searched archives, e.g. a ZIP archive in a TAR+GZIP archive. Matches are labeled \
+        with the full nesting breadcrumb (`outer.tar.gz!inner.zip!file.txt`). Nested archives \
+        are loaded in memory to be searched."
+    )]
+    nested: bool,
+
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = 3,
+        requires = "nested",
+        help = "Maximum number of nested archives to go through",
+        long_help = "Maximum number of nested archives to go through. Deeper archives are \
+        searched as plain files, which stops archives containing themselves (quines) from \
+        being expanded forever."
+    )]
+    max_nesting: usize,
+
+    #[arg(
+        long,
+        value_name = "SIZE",
+        value_parser = parse_size,
+        default_value = "1GB",
+        requires = "nested",
+        help = "Maximum total size of the nested archives loaded in memory for each file",
+        long_help = "Maximum total size of the nested archives, once decompressed, loaded in \
+        memory while searching a file. The search of the file is aborted when it is exceeded, \
+        to defend against nested decompression bombs."
+    )]
+    max_nested_size: Size,
+
+    #[arg(
+        short = 'j',
+        long,
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..),
+        help = "Number of files searched in parallel (default: number of CPUs)",
+        long_help = "Number of files searched in parallel, by default the number of CPUs. The \
+        results are printed in the order of the files given, whatever the order in which \
+        their search completes."
+    )]
+    threads: Option<u32>,
+
+    #[arg(
+        long,
+        value_name = "GLOB",
+        value_parser = parse_glob,
+        help = "Only search the files of archives matching GLOB (repeatable)",
+        long_help = "Only search the files of archives whose path matches one of the given \
+        globs (e.g. 'app/*.log'), the other files are not even decompressed. Globs without \
+        a slash are matched against the file name only (e.g. '*.log'), `**` matches any \
+        number of directories. Nested archives are searched whether they match or not."
+    )]
+    include: Vec<Pattern>,
+
+    #[arg(
+        long,
+        value_enum,
+        default_value_t = OutputFormat::Text,
+        conflicts_with_all = ["bytes", "files_with_matches", "count", "after_context", "before_context", "context"],
+        help = "Format of the matches",
+        long_help = "Format of the matches:\n\
+        - text: `archive!entry:line:text`, like grep\n\
+        - json: one JSON object per matching line (JSON Lines), with the `archive`, `entry`, \
+        `line`, `column` and `text` fields. `entry` is the path of the file in the archive \
+        (including the nested archives) or null for files which are not archives."
+    )]
+    format: OutputFormat,
+
+    #[arg(
+        required = true,
+        help = "Files to search",
+        value_name = "FILES",
+        long_help = "One or more archives or compressed files to search. Every file they \
+        contain is decompressed on the fly and searched line by line."
+    )]
+    files: Vec<PathBuf>,
+}
+
+/// ANSI color of the labels of the files, the same as grep
+const LABEL_COLOR: &str = "35";
+/// ANSI color of the line numbers and of the offsets
+const LINE_NUMBER_COLOR: &str = "32";
+/// ANSI color of the separators
+const SEPARATOR_COLOR: &str = "36";
+/// ANSI color of the matches
+const MATCH_COLOR: &str = "01;31";
+
+/// The formats in which matches can be printed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum OutputFormat {
+    Text,
+    Json,
+}
+
+/// Searches files for a pattern and prints the matching lines.
+#[derive(Debug, Clone)]
+struct Searcher<'c> {
+    /// The options of the command line, e.g. the limits of the decompression
+    context: &'c Context,
+    regex: Regex,
+    /// Number of lines printed before every matching line
+    before: usize,
+    /// Number of lines printed after every matching line
+    after: usize,
+    /// Whether a group of lines has been printed, to separate it from the next one
+    printed_group: bool,
+    files_with_matches: bool,
+    count: bool,
+    /// Search for a sequence of bytes anywhere in the files instead of matching lines
+    bytes: Option<usize>,
+    /// Number of nested archives to go through, 0 to search nested archives as plain files
+    max_nesting: usize,
+    /// Total size of the nested archives which can be loaded in memory for each file
+    max_nested_size: u64,
+    /// Total size of the nested archives loaded in memory for the file being searched
+    nested_size: u64,
+    format: OutputFormat,
+    /// Label of the input file being searched, which the labels of its entries start with
+    archive: String,
+    /// Globs the files of archives must match to be searched, see `--include`
+    include: Vec<Pattern>,
+}
+
+impl Searcher<'_> {
+    /// Searches every file of an input, whatever its format.
+    ///
+    /// # Arguments
+    /// * `label` - The label of the input, e.g. its path
+    /// * `file_path` - The path or the name of the input
+    /// * `reader` - A reader providing the raw content of the input
+    /// * `file_type` - The MIME type detected for the input
+    /// * `depth` - The number of archives the input is nested in
+    /// * `out` - Where to print the matches
+    ///
+    /// # Returns
+    /// * `Ok(true)` - If at least one file matched
+    /// * `Ok(false)` - If no file matched
+    /// * `Err(ZcatError)` - If the input could not be read or the output written
+    fn search_input<R, W>(
+        &mut self,
+        label: &str,
+        file_path: &Path,
+        reader: R,
+        file_type: &str,
+        depth: usize,
+        out: &mut W,
+    ) -> Result<bool, ZcatError>
+    where
+        R: Read + Seek,
+        W: Write,
+    {
+        let mut matched = false;
+        let context = self.context;
+        handle_entries(
+            context,
+            file_path,
+            reader,
+            file_type,
+            |entry_name, reader| {
+                matched |= match entry_name {
+                    Some(entry_name) => {
+                        let label = format!("{label}!{entry_name}");
+                        self.search_entry(&label, entry_name, reader, depth, out)?
+                    }
+                    None => self.search(label, reader, out)?,
+                };
+                Ok(())
+            },
+        )?;
+        Ok(matched)
+    }
+
+    /// Searches a file found in an archive, going through it when it is itself an archive.
+    ///
+    /// Files not matching `--include` are skipped without being decompressed, unless they
+    /// must be inspected to find out whether they are nested archives.
+    ///
+    /// # Arguments
+    /// * `label` - The label of the file, including the archives it is nested in
+    /// * `entry_name` - The name of the file in its archive
+    /// * `reader` - A reader providing the decompressed content of the file
+    /// * `depth` - The number of archives the file is nested in
+    /// * `out` - Where to print the matches
+    ///
+    /// # Returns
+    /// * `Ok(true)` - If the file or one of the files it contains matched
+    /// * `Ok(false)` - If nothing matched
+    /// * `Err(ZcatError)` - If the file could not be read or the output written
+    fn search_entry<W>(
+        &mut self,
+        label: &str,
+        entry_name: &str,
+        reader: &mut dyn Read,
+        depth: usize,
+        out: &mut W,
+    ) -> Result<bool, ZcatError>
+    where
+        W: Write,
+    {
+        let included = self.is_included(entry_name);
+        if depth >= self.max_nesting {
+            return match included {
+                true => Ok(self.search(label, reader, out)?),
+                false => Ok(false),
+            };
+        }
+
+        let mut magic_bytes = [0u8; MAGIC_BYTES_SIZE];
+        let read_bytes = read_up_to(reader, &mut magic_bytes)?;
+        let magic_bytes = &magic_bytes[..read_bytes];
+        let mut reader = io::Cursor::new(magic_bytes).chain(reader);
+        match detect_file_type(self.context, Some(entry_name), magic_bytes) {
+            file_type if self.context.registry.get(file_type).is_some() => {
+                // ZIP archives need to be seekable, so nested archives are loaded in memory
+                let mut content = Vec::new();
+                let budget = self.max_nested_size - self.nested_size;
+                read_to_end_limited(self.context, reader.take(budget + 1), &mut content)?;
+                if content.len() as u64 > budget {
+                    return Err(
+                        io::Error::other(ExpansionError::NestedSize(self.max_nested_size)).into(),
+                    );
+                }
+                self.nested_size += content.len() as u64;
+                let reader = io::Cursor::new(content);
+                let file_path = Path::new(entry_name);
+                self.search_input(label, file_path, reader, file_type, depth + 1, out)
+            }
+            _ if included => Ok(self.search(label, &mut reader, out)?),
+            _ => Ok(false),
+        }
+    }
+
+    /// Tells whether a file of an archive matches the globs of `--include`, if any.
+    ///
+    /// # Arguments
+    /// * `entry_name` - The path of the file in its archive
+    fn is_included(&self, entry_name: &str) -> bool {
+        self.include.is_empty() || matches_globs(&self.include, entry_name)
+    }
+
+    /// Creates a searcher from the options of the command line and the arguments of the
+    /// subcommand.
+    ///
+    /// # Errors
+    /// Returns a description of the error if the pattern is invalid
+    fn new<'c>(context: &'c Context, args: &GrepArgs) -> Result<Searcher<'c>, String> {
+        let mut bytes = None;
+        let mut pattern = match (args.bytes, args.fixed_strings) {
+            (true, _) => {
+                let sequence = parse_hex_bytes(&args.pattern)?;
+                bytes = Some(sequence.len());
+                sequence
+                    .iter()
+                    .map(|byte| format!(r"\x{byte:02x}"))
+                    .collect()
+            }
+            (false, true) => regex::escape(&args.pattern),
+            (false, false) => args.pattern.clone(),
+        };
+        if args.word_regexp {
+            pattern = format!(r"\b(?:{pattern})\b");
+        }
+
+        Ok(Searcher {
+            context,
+            regex: RegexBuilder::new(&pattern)
+                .case_insensitive(args.ignore_case)
+                // Bytes must be matched as such, not as the UTF-8 encoding of code points
+                .unicode(!args.bytes)
+                .build()
+                .map_err(|err| err.to_string())?,
+            before: args.before_context.or(args.context).unwrap_or(0),
+            after: args.after_context.or(args.context).unwrap_or(0),
+            printed_group: false,
+            files_with_matches: args.files_with_matches,
+            count: args.count,
+            bytes,
+            max_nesting: match args.nested {
+                true => args.max_nesting,
+                false => 0,
+            },
+            max_nested_size: args.max_nested_size.bytes(context.size_units),
+            nested_size: 0,
+            format: args.format,
+            archive: String::new(),
+            include: args.include.clone(),
+        })
+    }
+
+    /// Searches the lines of a file for the pattern and prints the matching ones,
+    /// along with the requested context lines.
+    ///
+    /// Like `grep`, only a notice is printed for binary files (containing NUL bytes)
+    /// instead of the matching lines. With `-l` or `-c`, only the label of the file
+    /// or its number of matching lines is printed.
+    ///
+    /// # Arguments
+    /// * `label` - The label of the file, printed before every line
+    /// * `reader` - A reader providing the decompressed content of the file
+    /// * `out` - Where to print the lines
+    ///
+    /// # Returns
+    /// * `Ok(true)` - If at least one line matched
+    /// * `Ok(false)` - If no line matched
+    /// * `Err(io::Error)` - If the content could not be read or the output written
+    fn search<W>(&mut self, label: &str, reader: &mut dyn Read, out: &mut W) -> io::Result<bool>
+    where
+        W: Write,
+    {
+        if let Some(length) = self.bytes {
+            return self.search_bytes(length, label, reader, out);
+        }
+
+        let mut reader = BufReader::new(reader);
+        let mut line = Vec::new();
+        let mut line_number = 0;
+        let mut matched = false;
+        // Lines kept to be printed as leading context of the next match
+        let mut previous_lines: VecDeque<(usize, Vec<u8>)> = VecDeque::with_capacity(self.before);
+        let mut last_printed = 0;
+        let mut trailing_lines = 0;
+        let mut count = 0;
+        loop {
+            line.clear();
+            if reader.read_until(b'\n', &mut line)? == 0 {
+                if self.count {
+                    writeln!(
+                        out,
+                        "{}{}{count}",
+                        paint(self.context, label, LABEL_COLOR),
+                        paint(self.context, ":", SEPARATOR_COLOR)
+                    )?;
+                }
+                return Ok(matched);
+            }
+            line_number += 1;
+
+            if !self.regex.is_match(&line) {
+                if trailing_lines > 0 {
+                    trailing_lines -= 1;
+                    last_printed = line_number;
+                    self.print_line(out, label, '-', line_number, &line)?;
+                } else if self.before > 0 {
+                    if previous_lines.len() == self.before {
+                        previous_lines.pop_front();
+                    }
+                    previous_lines.push_back((line_number, line.clone()));
+                }
+                continue;
+            }
+            matched = true;
+            if self.files_with_matches {
+                writeln!(out, "{}", paint(self.context, label, LABEL_COLOR))?;
+                return Ok(true);
+            }
+            if self.count {
+                count += 1;
+                continue;
+            }
+            if self.format == OutputFormat::Json {
+                self.print_json_match(out, label, line_number, &line)?;
+                continue;
+            }
+            if line.contains(&0) {
+                writeln!(out, "Binary file {label} matches")?;
+                return Ok(true);
+            }
+
+            let first_line = previous_lines
+                .front()
+                .map_or(line_number, |(number, _)| *number);
+            let is_contiguous = last_printed > 0 && first_line <= last_printed + 1;
+            if (self.before > 0 || self.after > 0) && self.printed_group && !is_contiguous {
+                writeln!(out, "{}", paint(self.context, "--", SEPARATOR_COLOR))?;
+            }
+            for (number, previous_line) in previous_lines.drain(..) {
+                self.print_line(out, label, '-', number, &previous_line)?;
+            }
+            self.print_line(out, label, ':', line_number, &line)?;
+            self.printed_group = true;
+            last_printed = line_number;
+            trailing_lines = self.after;
+        }
+    }
+
+    /// Prints a line of a file, prefixed with the label of the file and the line number.
+    ///
+    /// The matches of matching lines are highlighted when colors are enabled.
+    ///
+    /// # Arguments
+    /// * `out` - Where to print the line
+    /// * `label` - The label of the file
+    /// * `separator` - `:` for matching lines and `-` for context lines
+    /// * `line_number` - The number of the line in the file, starting at 1
+    /// * `line` - The content of the line, including its line ending
+    fn print_line<W>(
+        &self,
+        out: &mut W,
+        label: &str,
+        separator: char,
+        line_number: usize,
+        line: &[u8],
+    ) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let text = line.strip_suffix(b"\n").unwrap_or(line);
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+        let mut highlighted = Vec::with_capacity(text.len());
+        let mut end = 0;
+        if self.context.color && separator == ':' {
+            for found in self.regex.find_iter(text) {
+                highlighted.extend_from_slice(&text[end..found.start()]);
+                write!(highlighted, "\x1b[{MATCH_COLOR}m")?;
+                highlighted.extend_from_slice(found.as_bytes());
+                highlighted.extend_from_slice(b"\x1b[m");
+                end = found.end();
+            }
+        }
+        highlighted.extend_from_slice(&text[end..]);
+
+        let separator = paint(self.context, &separator.to_string(), SEPARATOR_COLOR);
+        writeln!(
+            out,
+            "{}{separator}{}{separator}{}",
+            paint(self.context, label, LABEL_COLOR),
+            paint(self.context, &line_number.to_string(), LINE_NUMBER_COLOR),
+            String::from_utf8_lossy(&highlighted)
+        )
+    }
+
+    /// Prints a matching line as a JSON object, see `--format json`.
+    ///
+    /// # Arguments
+    /// * `out` - Where to print the object
+    /// * `label` - The label of the file
+    /// * `line_number` - The number of the line in the file, starting at 1
+    /// * `line` - The content of the line, including its line ending
+    fn print_json_match<W>(
+        &self,
+        out: &mut W,
+        label: &str,
+        line_number: usize,
+        line: &[u8],
+    ) -> io::Result<()>
+    where
+        W: Write,
+    {
+        let entry = label
+            .strip_prefix(&self.archive)
+            .and_then(|entry| entry.strip_prefix('!'));
+        let text = line.strip_suffix(b"\n").unwrap_or(line);
+        let text = text.strip_suffix(b"\r").unwrap_or(text);
+        // Column of the first match, in characters and starting at 1
+        let start = self.regex.find(text).map_or(0, |found| found.start());
+        let column = String::from_utf8_lossy(&text[..start]).chars().count() + 1;
+        let object = serde_json::json!({
+            "archive": self.archive,
+            "entry": entry,
+            "line": line_number,
+            "column": column,
+            "text": String::from_utf8_lossy(text),
+        });
+        writeln!(out, "{object}")
+    }
+
+    /// Searches a file for a sequence of bytes and prints the offset of every occurrence.
+    ///
+    /// The file is read by chunks, keeping the end of the previous chunk so that
+    /// occurrences straddling two chunks are found, including overlapping ones.
+    ///
+    /// # Arguments
+    /// * `length` - The length of the sequence of bytes
+    /// * `label` - The label of the file, printed before every offset
+    /// * `reader` - A reader providing the decompressed content of the file
+    /// * `out` - Where to print the offsets
+    ///
+    /// # Returns
+    /// * `Ok(true)` - If the sequence was found
+    /// * `Ok(false)` - If the sequence was not found
+    /// * `Err(io::Error)` - If the content could not be read or the output written
+    fn search_bytes<W>(
+        &self,
+        length: usize,
+        label: &str,
+        reader: &mut dyn Read,
+        out: &mut W,
+    ) -> io::Result<bool>
+    where
+        W: Write,
+    {
+        let mut window = Vec::with_capacity(BUFFER_SIZE + length);
+        // Offset in the file of the first byte of the window
+        let mut window_offset = 0;
+        let mut buffer = [0u8; BUFFER_SIZE];
+        let mut count = 0;
+        loop {
+            let read_bytes = match reader.read(&mut buffer) {
+                Ok(0) => break,
+                Ok(read_bytes) => read_bytes,
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            };
+            window.extend_from_slice(&buffer[..read_bytes]);
+
+            let mut start = 0;
+            while let Some(found) = self.regex.find_at(&window, start) {
+                count += 1;
+                if self.files_with_matches {
+                    writeln!(out, "{}", paint(self.context, label, LABEL_COLOR))?;
+                    return Ok(true);
+                }
+                if !self.count {
+                    let offset = format!("{:#x}", window_offset + found.start() as u64);
+                    writeln!(
+                        out,
+                        "{}{}{}",
+                        paint(self.context, label, LABEL_COLOR),
+                        paint(self.context, ":", SEPARATOR_COLOR),
+                        paint(self.context, &offset, LINE_NUMBER_COLOR)
+                    )?;
+                }
+                start = found.start() + 1;
+            }
+
+            // Only keep the bytes which may start an occurrence completed by the next chunk
+            let kept = window.len().min(length - 1);
+            window_offset += (window.len() - kept) as u64;
+            window.drain(..window.len() - kept);
+        }
+
+        if self.count {
+            writeln!(
+                out,
+                "{}{}{count}",
+                paint(self.context, label, LABEL_COLOR),
+                paint(self.context, ":", SEPARATOR_COLOR)
+            )?;
+        }
+        Ok(count > 0)
+    }
+}
+
+/// The outcome of the search of an input file.
+#[derive(Debug, Default)]
+struct FileSearch {
+    matched: bool,
+    /// Whether a group of lines was printed, to separate it from the groups of the other files
+    printed_group: bool,
+    /// The error which stopped the search
+    error: Option<String>,
+}
+
+impl Searcher<'_> {
+    /// Searches an input file.
+    ///
+    /// The groups of lines are separated from those the searcher printed for the previous
+    /// files, a new searcher being needed for the files printed out of its sight.
+    ///
+    /// # Arguments
+    /// * `file_path` - The path of the file to search
+    /// * `out` - Where to print the matches
+    ///
+    /// # Returns
+    /// Whether the search matched or failed
+    fn search_file<W>(&mut self, file_path: &Path, out: &mut W) -> FileSearch
+    where
+        W: Write,
+    {
+        let _span = tracing::info_span!("input", file = %file_path.display()).entered();
+        let mut search = FileSearch::default();
+        let opened = Input::open(self.context, file_path)
+            .and_then(|mut input| Ok((input.file_type(self.context, file_path)?, input)));
+        let (file_type, input) = match opened {
+            Ok(opened) => opened,
+            Err(err) => {
+                search.error = Some(format!(
+                    "Could not read the following file: {file_path:?}. Error: {err}"
+                ));
+                return search;
+            }
+        };
+
+        self.nested_size = 0;
+        let label = file_path.display().to_string();
+        self.archive = label.clone();
+        let output = self.search_input(&label, file_path, input, file_type, 0, out);
+        match output {
+            Ok(matched) => search.matched = matched,
+            Err(err) => {
+                search.error = Some(format!(
+                    "An error occurred while searching the file: {file_path:?}. Error: {err}"
+                ))
+            }
+        }
+        search.printed_group = self.printed_group;
+        search
+    }
+}
+
+/// Runs the `grep` subcommand.
+///
+/// The files are searched in parallel by a pool of threads, what the search of each file
+/// prints being buffered to be printed in the order of the files (see `for_each_in_order`).
+/// A single file, or those searched by a single thread, are printed as they are searched.
+/// Errors are reported on stderr without stopping the search of the other files.
+///
+/// # Arguments
+/// * `context` - The options of the command line
+/// * `args` - The arguments of the subcommand
+///
+/// # Returns
+/// The exit code, following the convention of `grep`: 0 if a line matched,
+/// 1 if no line matched and 2 if an error occurred
+pub(crate) fn run(context: &Context, args: &GrepArgs) -> i32 {
+    let searcher = match Searcher::new(context, args) {
+        Ok(searcher) => searcher,
+        Err(err) => {
+            eprintln!("Invalid pattern: {err}");
+            return 2;
+        }
+    };
+    let with_context = searcher.before > 0 || searcher.after > 0;
+    let files = expand_globs(&args.files);
+    let threads = args
+        .threads
+        .map(|threads| threads as usize)
+        .unwrap_or_else(|| thread::available_parallelism().map_or(1, NonZeroUsize::get))
+        .min(files.len());
+
+    let mut matched = false;
+    let mut failed = false;
+    let run_stats = InputStats::start();
+    // Searched one after the other for the statistics of every file to be its own
+    if threads <= 1 || context.stats {
+        let mut searcher = searcher;
+        for file_path in &files {
+            let stats = InputStats::start();
+            let mut out = output::stdout();
+            let search = searcher.search_file(file_path, &mut *out);
+            matched |= search.matched;
+            failed |= report_error(&mut *out, search.error);
+            drop(out);
+            stats.report(context, &format!("{file_path:?}"));
+        }
+    } else {
+        let mut out = output::stdout();
+        let mut printed_group = false;
+        let search = |file_path: &PathBuf| {
+            let mut output = Vec::new();
+            let search = searcher.clone().search_file(file_path, &mut output);
+            (output, search)
+        };
+        for_each_in_order(&files, threads, search, |(output, search)| {
+            if with_context && printed_group && search.printed_group {
+                failed |= writeln!(out, "{}", paint(context, "--", SEPARATOR_COLOR)).is_err();
+            }
+            failed |= out.write_all(&output).is_err();
+            failed |= report_error(&mut *out, search.error);
+            matched |= search.matched;
+            printed_group |= search.printed_group;
+        });
+    }
+    output::flush();
+    run_stats.report_total(context, files.len());
+
+    match (failed, matched) {
+        (true, _) => 2,
+        (false, true) => 0,
+        (false, false) => 1,
+    }
+}
+
+/// Reports the error which stopped the search of a file, after what the search printed.
+///
+/// # Returns
+/// Whether there was an error
+fn report_error<W: Write>(out: &mut W, error: Option<String>) -> bool {
+    let Some(error) = error else {
+        return false;
+    };
+    let _ = out.flush();
+    eprintln!("{error}");
+    true
+}
@@ -0,0 +1,2462 @@
+//! Reading of compressed files and archives, the core of the `zcatr` command.
+//!
+//! The format of an input is detected from its first bytes (see `detect_format`), then
+//! `for_each_entry` hands over the decompressed content of every file it holds, whatever its
+//! format: the entries of ZIP and TAR archives, including the TAR archives compressed with
+//! GZIP or BZIP2, or the single file of a compressed file.
+//!
+//! ```no_run
+//! use std::{fs::File, io};
+//!
+//! let mut file = File::open("release.tar.gz")?;
+//! let mime_type = zcatr::detect_format(&mut file)?;
+//! zcatr::for_each_entry("release.tar.gz", file, mime_type, &(), |name, content| {
+//!     let size = io::copy(content, &mut io::sink())?;
+//!     println!("{}: {size} bytes", name.unwrap_or("-"));
+//!     Ok::<_, zcatr::Error>(())
+//! })?;
+//! # Ok::<_, zcatr::Error>(())
+//! ```
+//!
+//! The settings of the reading, such as the password of encrypted ZIP entries or the limits of
+//! the decompression, are given by implementing `Options`.
+//!
+//! # Features
+//! * `bzip2` - BZIP2 files and the BZIP2 entries of ZIP archives
+//! * `xz` - XZ files, decompressed by several threads when large
+//! * `zstd` - ZSTD files and the ZSTD entries of ZIP archives
+//! * `aes` - The AES-encrypted entries of ZIP archives
+//! * `cli` (default) - The `zcatr` command, with all the above
+//!
+//! The library never touches the file system, the inputs being readers. Without its default
+//! features, it builds for `wasm32-unknown-unknown`, e.g. to preview the archives uploaded to
+//! a web page (the features above need C libraries or a source of randomness):
+//! ```text
+//! cargo build --lib --no-default-features --target wasm32-unknown-unknown
+//! ```
+
+use std::{
+    fmt,
+    io::{self, Read, Seek},
+    marker::PhantomData,
+    sync::OnceLock,
+    thread,
+};
+
+use chrono::{DateTime, Local, NaiveDate, TimeZone, Utc};
+use flate2::read::{GzDecoder, MultiGzDecoder};
+use thiserror::Error;
+use tracing::debug;
+
+/// How many bytes are read from the start of a stream to detect its format.
+pub const MAGIC_BYTES_SIZE: usize = 512;
+
+/// The size from which the GZIP files made of independent blocks are decompressed by several
+/// threads, see `open_gzip`.
+pub const PARALLEL_GZIP_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// The size from which the XZ files are decompressed by several threads, see `open_xz`.
+pub const PARALLEL_XZ_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// The size from which the ZSTD files made of several frames are decompressed by several
+/// threads, see `open_zstd`.
+pub const PARALLEL_ZSTD_THRESHOLD: u64 = 16 * 1024 * 1024;
+
+/// How many blocks of a BGZF file every thread decompresses at once, see `ParallelDecoder`.
+const BLOCKS_PER_THREAD: usize = 16;
+
+/// The size from which a ZSTD frame is decompressed by a single thread along with the rest of
+/// the file, rather than loaded in memory to be decompressed in parallel with the others.
+#[cfg(feature = "zstd")]
+const MAX_PARALLEL_FRAME_SIZE: usize = 8 * 1024 * 1024;
+
+/// An error reading an input.
+#[derive(Error, Debug)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("ZIP error: {0}")]
+    Zip(#[from] zip::result::ZipError),
+    /// The handler of the entries failed, see `Registry::for_each_entry`
+    #[error("the handler of the entries failed")]
+    Handler,
+}
+
+/// The settings and callbacks of the reading of the inputs, all optional: `()` reads them
+/// with the defaults.
+pub trait Options {
+    /// Whether the directories of the archives are handed over too, with an empty content.
+    fn include_dirs(&self) -> bool {
+        false
+    }
+
+    /// Returns the password of the encrypted ZIP entries, only asked for when an archive
+    /// holds some. Without password, reading them fails.
+    fn password(&self) -> Option<&str> {
+        None
+    }
+
+    /// Wraps the decompressed content of the files before it is read, e.g. to limit how
+    /// much is decompressed.
+    ///
+    /// # Arguments
+    /// * `content` - The decompressed content
+    /// * `compressed_size` - The size of the compressed content, `None` when unknown (e.g. for
+    ///   the standard input)
+    fn wrap<'r>(
+        &'r self,
+        content: Box<dyn Read + 'r>,
+        compressed_size: Option<u64>,
+    ) -> Box<dyn Read + 'r> {
+        let _ = compressed_size;
+        content
+    }
+
+    /// Called before every entry of an archive is handed over, e.g. to report the progress.
+    fn on_entry(&self) {}
+
+    /// Returns how many bytes of an input may be loaded in memory at once, by the formats
+    /// which cannot stream it. No limit by default.
+    fn max_memory(&self) -> Option<u64> {
+        None
+    }
+
+    /// Returns the number of threads decompressing the large GZIP and ZSTD files made of
+    /// independent blocks and the large XZ files, see `open_gzip`, `open_zstd` and `open_xz`.
+    /// One by default, the files being decompressed by the calling thread.
+    fn threads(&self) -> usize {
+        1
+    }
+}
+
+/// The default options.
+impl Options for () {}
+
+/// Reads from a reader until the buffer is full or the end of the stream is reached.
+///
+/// # Arguments
+/// * `reader` - The reader to read from
+/// * `buffer` - The buffer to fill
+///
+/// # Returns
+/// * `Ok(usize)` - The number of bytes read, lower than the buffer size only at the end of the stream
+/// * `Err(io::Error)` - If the reader failed
+pub fn read_up_to<R>(reader: &mut R, buffer: &mut [u8]) -> io::Result<usize>
+where
+    R: Read + ?Sized,
+{
+    let mut filled = 0;
+    while filled < buffer.len() {
+        match reader.read(&mut buffer[filled..]) {
+            Ok(0) => break,
+            Ok(read_bytes) => filled += read_bytes,
+            Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+            Err(err) => return Err(err),
+        }
+    }
+    Ok(filled)
+}
+
+/// Determines the MIME type of some content from its first bytes.
+///
+/// Content that `infer` does not recognize is reported as `text/plain` when it
+/// looks like UTF-8 text and as `application/octet-stream` otherwise.
+///
+/// # Arguments
+/// * `magic_bytes` - The first bytes of the content
+///
+/// # Returns
+/// The MIME type of the content
+pub fn detect_mime_type(magic_bytes: &[u8]) -> &'static str {
+    if magic_bytes.is_empty() {
+        return "application/x-empty";
+    }
+
+    if let Some(mime_type) = infer::get(magic_bytes) {
+        return mime_type.mime_type();
+    }
+
+    // The first bytes may end in the middle of a multi-byte character
+    let is_text = match std::str::from_utf8(magic_bytes) {
+        Ok(_) => true,
+        Err(err) => err.error_len().is_none(),
+    };
+    if is_text && !magic_bytes.contains(&0) {
+        "text/plain"
+    } else {
+        "application/octet-stream"
+    }
+}
+
+/// Detects the format of an input from its first bytes, leaving it at its start.
+///
+/// # Returns
+/// * `Ok(&str)` - The MIME type of the input, e.g. `application/gzip`, or an empty string
+///   when it is not recognized, its content being then handed over as is by `for_each_entry`
+/// * `Err(io::Error)` - If the input could not be read
+pub fn detect_format<R: Read + Seek>(reader: &mut R) -> io::Result<&'static str> {
+    let mut magic_bytes = [0u8; MAGIC_BYTES_SIZE];
+    let read_bytes = read_up_to(reader, &mut magic_bytes)?;
+    reader.rewind()?;
+    Ok(infer::get(&magic_bytes[..read_bytes]).map_or("", |file_type| file_type.mime_type()))
+}
+
+/// Tells whether a stream can be seeked, which the standard input cannot.
+pub fn is_seekable<S: Seek>(stream: &mut S) -> bool {
+    stream.stream_position().is_ok()
+}
+
+/// Returns the length of a stream positioned at its start, leaving it there.
+pub fn stream_len<S: Seek>(stream: &mut S) -> io::Result<u64> {
+    let len = stream.seek(io::SeekFrom::End(0))?;
+    stream.rewind()?;
+    Ok(len)
+}
+
+/// Returns the name of the file compressed in a file, without the compression extension:
+/// `notes.txt` for `notes.txt.gz`, `backup.tar` for `backup.tgz`.
+///
+/// # Arguments
+/// * `name` - The name of the compressed file
+pub fn decompressed_name(name: &str) -> String {
+    match name.rsplit_once('.') {
+        Some((stem, "tgz" | "tbz" | "tbz2" | "txz" | "tzst")) => format!("{stem}.tar"),
+        Some((stem, _)) => stem.to_owned(),
+        None => name.to_owned(),
+    }
+}
+
+/// Metadata stored in the header of a GZIP file.
+#[derive(Debug, Default)]
+pub struct GzipMetadata {
+    /// Original name of the compressed file (FNAME)
+    pub file_name: Option<String>,
+    pub modified: Option<DateTime<Utc>>,
+    /// Operating system on which the compression took place
+    pub system: Option<&'static str>,
+    pub comment: Option<String>,
+}
+
+impl GzipMetadata {
+    /// Reads the header of a GZIP stream.
+    ///
+    /// The header is parsed without consuming any of the compressed content.
+    ///
+    /// # Arguments
+    /// * `decoder` - The GZIP decoder to read the header from
+    ///
+    /// # Returns
+    /// * `Ok(GzipMetadata)` - The metadata found in the header
+    /// * `Err(io::Error)` - If the header is invalid
+    pub fn read<R>(decoder: &mut GzDecoder<R>) -> io::Result<Self>
+    where
+        R: Read,
+    {
+        // Reading into an empty buffer forces the header to be parsed
+        let _ = decoder.read(&mut [])?;
+        Ok(GzipMetadata::from_header(decoder.header()))
+    }
+
+    /// Returns the metadata of a parsed header, all unset without header.
+    fn from_header(header: Option<&flate2::GzHeader>) -> Self {
+        let Some(header) = header else {
+            return GzipMetadata::default();
+        };
+
+        let system = match header.operating_system() {
+            0 => Some("FAT"),
+            1 => Some("Amiga"),
+            2 => Some("VMS"),
+            3 => Some("Unix"),
+            4 => Some("VM/CMS"),
+            5 => Some("Atari TOS"),
+            6 => Some("HPFS"),
+            7 => Some("Macintosh"),
+            8 => Some("Z-System"),
+            9 => Some("CP/M"),
+            10 => Some("TOPS-20"),
+            11 => Some("NTFS"),
+            12 => Some("QDOS"),
+            13 => Some("Acorn RISCOS"),
+            _ => None,
+        };
+        let latin1 = |bytes: &[u8]| bytes.iter().map(|&byte| byte as char).collect::<String>();
+
+        GzipMetadata {
+            file_name: header.filename().map(latin1),
+            // A zero timestamp means that no time is available
+            modified: Some(header.mtime())
+                .filter(|&mtime| mtime != 0)
+                .and_then(|mtime| DateTime::from_timestamp(mtime.into(), 0)),
+            system,
+            comment: header.comment().map(latin1),
+        }
+    }
+}
+
+/// Opens a GZIP stream, returning the metadata of its header along with its decompressed
+/// content, that of all its members like `gzip -d` does.
+///
+/// Files made of independent blocks, such as those of bgzip (BGZF), are decompressed by
+/// `Options::threads` threads once they reach `PARALLEL_GZIP_THRESHOLD`, the blocks being
+/// decompressed by batches while the next batch is read.
+///
+/// # Arguments
+/// * `input` - The compressed stream, from its start
+/// * `compressed_size` - The size of the compressed stream, `None` when unknown (e.g. for
+///   the standard input)
+/// * `options` - The settings of the reading
+///
+/// # Returns
+/// * `Ok((GzipMetadata, Box<dyn Read>))` - The metadata of the first member and the content
+/// * `Err(io::Error)` - If the header could not be read or is invalid
+pub fn open_gzip<'r, R>(
+    mut input: R,
+    compressed_size: Option<u64>,
+    options: &dyn Options,
+) -> io::Result<(GzipMetadata, Box<dyn Read + 'r>)>
+where
+    R: Read + 'r,
+{
+    let threads = options.threads();
+    let mut head = vec![0u8; MAGIC_BYTES_SIZE];
+    let read_bytes = read_up_to(&mut input, &mut head)?;
+    head.truncate(read_bytes);
+    let large = compressed_size.is_some_and(|size| size >= PARALLEL_GZIP_THRESHOLD);
+    let input = io::Cursor::new(head).chain(input);
+
+    if threads > 1 && large && bgzf_header_len(input.get_ref().0.get_ref()).is_some() {
+        // The first block holds the header of the file
+        let mut decoder = GzDecoder::new(input.get_ref().0.get_ref().as_slice());
+        let metadata = GzipMetadata::read(&mut decoder).unwrap_or_default();
+        debug!(
+            threads,
+            "decompressing the blocks of the BGZF file in parallel"
+        );
+        return Ok((
+            metadata,
+            Box::new(ParallelDecoder::<_, Bgzf>::new(input, threads)),
+        ));
+    }
+    let mut decoder = MultiGzDecoder::new(input);
+    let _ = decoder.read(&mut [])?;
+    let metadata = GzipMetadata::from_header(decoder.header());
+    Ok((metadata, Box::new(decoder)))
+}
+
+/// Opens an XZ stream, returning its decompressed content, that of all its concatenated
+/// streams like `xz -d` does.
+///
+/// Files reaching `PARALLEL_XZ_THRESHOLD` are decompressed by `Options::threads` threads with
+/// the multithreaded decoder of liblzma, which decompresses in parallel the blocks of the
+/// files compressed by several threads (e.g. `xz -T0`), and uses a single thread otherwise.
+/// The memory it uses is limited by `Options::max_memory`, above which it decompresses in a
+/// single thread too.
+///
+/// # Arguments
+/// * `input` - The compressed stream, from its start
+/// * `compressed_size` - The size of the compressed stream, `None` when unknown (e.g. for
+///   the standard input)
+/// * `options` - The settings of the reading
+///
+/// # Returns
+/// * `Ok(Box<dyn Read>)` - The decompressed content
+/// * `Err(io::Error)` - If the multithreaded decoder could not be created
+#[cfg(feature = "xz")]
+pub fn open_xz<'r, R>(
+    input: R,
+    compressed_size: Option<u64>,
+    options: &dyn Options,
+) -> io::Result<Box<dyn Read + 'r>>
+where
+    R: Read + 'r,
+{
+    let threads = options.threads();
+    let large = compressed_size.is_some_and(|size| size >= PARALLEL_XZ_THRESHOLD);
+    if threads > 1 && large {
+        debug!(
+            threads,
+            "decompressing the blocks of the XZ file in parallel"
+        );
+        let memory_limit = options.max_memory().unwrap_or(u64::MAX);
+        return Ok(Box::new(ParallelXzDecoder::new(
+            io::BufReader::new(input),
+            threads,
+            memory_limit,
+        )?));
+    }
+    Ok(Box::new(liblzma::read::XzDecoder::new_multi_decoder(input)))
+}
+
+/// A decoder of the XZ files decompressing their blocks in parallel, see `open_xz`.
+///
+/// The multithreaded decoder of liblzma reads a single stream, so a new one is created for
+/// every stream of the concatenated files, after the padding between them.
+#[cfg(feature = "xz")]
+struct ParallelXzDecoder<R: io::BufRead> {
+    /// The decoder of the current stream, replaced at the start of the next one
+    decoder: Option<liblzma::bufread::XzDecoder<R>>,
+    threads: u32,
+    memory_limit: u64,
+}
+
+#[cfg(feature = "xz")]
+impl<R: io::BufRead> ParallelXzDecoder<R> {
+    fn new(input: R, threads: usize, memory_limit: u64) -> io::Result<Self> {
+        let threads = u32::try_from(threads).unwrap_or(u32::MAX);
+        let stream = Self::stream(threads, memory_limit)?;
+        Ok(ParallelXzDecoder {
+            decoder: Some(liblzma::bufread::XzDecoder::new_stream(input, stream)),
+            threads,
+            memory_limit,
+        })
+    }
+
+    /// Creates the multithreaded decoder of a stream.
+    fn stream(threads: u32, memory_limit: u64) -> io::Result<liblzma::stream::Stream> {
+        liblzma::stream::MtStreamBuilder::new()
+            .threads(threads)
+            .memlimit_threading(memory_limit)
+            // Above the limit, the blocks are decompressed by a single thread
+            .memlimit_stop(u64::MAX)
+            .decoder()
+            .map_err(io::Error::from)
+    }
+
+    /// Skips the padding after a stream, made of null bytes.
+    ///
+    /// # Returns
+    /// Whether another stream follows
+    fn skip_padding(&mut self) -> io::Result<bool> {
+        let input = self
+            .decoder
+            .as_mut()
+            .expect("the decoder is replaced at once")
+            .get_mut();
+        loop {
+            let buffer = input.fill_buf()?;
+            if buffer.is_empty() {
+                return Ok(false);
+            }
+            let padding = buffer.iter().take_while(|&&byte| byte == 0).count();
+            let next_stream = padding < buffer.len();
+            input.consume(padding);
+            if next_stream {
+                return Ok(true);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "xz")]
+impl<R: io::BufRead> Read for ParallelXzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read_bytes = self
+                .decoder
+                .as_mut()
+                .expect("the decoder is replaced at once")
+                .read(buf)?;
+            if read_bytes > 0 || buf.is_empty() || !self.skip_padding()? {
+                return Ok(read_bytes);
+            }
+            let stream = Self::stream(self.threads, self.memory_limit)?;
+            let input = self
+                .decoder
+                .take()
+                .expect("the decoder is replaced at once")
+                .into_inner();
+            self.decoder = Some(liblzma::bufread::XzDecoder::new_stream(input, stream));
+        }
+    }
+}
+
+/// Opens a ZSTD stream, returning its decompressed content, that of all its frames like
+/// `zstd -d` does.
+///
+/// Files made of several frames, such as those of pzstd, are decompressed by
+/// `Options::threads` threads once they reach `PARALLEL_ZSTD_THRESHOLD`, the frames being
+/// decompressed by batches while the next batch is read.
+///
+/// # Arguments
+/// * `input` - The compressed stream, from its start
+/// * `compressed_size` - The size of the compressed stream, `None` when unknown (e.g. for
+///   the standard input)
+/// * `options` - The settings of the reading
+///
+/// # Returns
+/// * `Ok(Box<dyn Read>)` - The decompressed content
+/// * `Err(io::Error)` - If the decoder could not be created
+#[cfg(feature = "zstd")]
+pub fn open_zstd<'r, R>(
+    input: R,
+    compressed_size: Option<u64>,
+    options: &dyn Options,
+) -> io::Result<Box<dyn Read + 'r>>
+where
+    R: Read + 'r,
+{
+    let threads = options.threads();
+    let large = compressed_size.is_some_and(|size| size >= PARALLEL_ZSTD_THRESHOLD);
+    if threads > 1 && large {
+        debug!(
+            threads,
+            "decompressing the frames of the ZSTD file in parallel"
+        );
+        return Ok(Box::new(ParallelDecoder::<_, ZstdFrames>::new(
+            input, threads,
+        )));
+    }
+    Ok(Box::new(zstd::stream::read::Decoder::new(input)?))
+}
+
+/// Returns the length of the header of a BGZF block, and the size of the whole block, if some
+/// bytes start with one.
+///
+/// BGZF blocks are GZIP members whose extra field holds a `BC` subfield giving their size,
+/// so that they can be decompressed independently of each other.
+fn bgzf_header_len(bytes: &[u8]) -> Option<(usize, usize)> {
+    // ID1, ID2, CM (DEFLATE) and FLG with FEXTRA
+    if bytes.len() < 12 || bytes[..3] != [0x1f, 0x8b, 0x08] || bytes[3] & 0x04 == 0 {
+        return None;
+    }
+    let extra_len = u16::from_le_bytes([bytes[10], bytes[11]]) as usize;
+    let mut extra = bytes.get(12..12 + extra_len)?;
+    while extra.len() >= 4 {
+        let field_len = u16::from_le_bytes([extra[2], extra[3]]) as usize;
+        let field = extra.get(4..4 + field_len)?;
+        if extra[..2] == *b"BC" && field_len == 2 {
+            let block_size = u16::from_le_bytes([field[0], field[1]]) as usize + 1;
+            return Some((12 + extra_len, block_size));
+        }
+        extra = &extra[4 + field_len..];
+    }
+    None
+}
+
+/// A compression format whose files can be made of independent blocks, decompressed in
+/// parallel by a `ParallelDecoder`.
+trait Blocks {
+    /// How many blocks every thread decompresses at once.
+    const PER_THREAD: usize;
+
+    /// Reads the next block of a stream.
+    ///
+    /// # Returns
+    /// * `Ok(Ok(Vec<u8>))` - The compressed block
+    /// * `Ok(Err(Vec<u8>))` - The bytes read from what is not a block to decompress in
+    ///   parallel, or from the end of the stream
+    /// * `Err(io::Error)` - If the stream could not be read or ends in the middle of a block
+    fn read_block<R: Read>(input: &mut R) -> io::Result<Result<Vec<u8>, Vec<u8>>>;
+
+    /// Decompresses a block, appending its content to the decompressed bytes.
+    fn decompress_block(block: &[u8], decompressed: &mut Vec<u8>) -> io::Result<()>;
+
+    /// Returns the decoder of the rest of a stream, from the first bytes which are not a block
+    /// to decompress in parallel.
+    fn serial<'r, R: Read + 'r>(rest: Rewound<R>) -> io::Result<Box<dyn Read + 'r>>;
+}
+
+/// The blocks of the BGZF files, see `bgzf_header_len`. The rest of a file is decompressed
+/// from the first member which is not a BGZF block.
+struct Bgzf;
+
+impl Blocks for Bgzf {
+    const PER_THREAD: usize = BLOCKS_PER_THREAD;
+
+    fn read_block<R: Read>(input: &mut R) -> io::Result<Result<Vec<u8>, Vec<u8>>> {
+        let mut block = vec![0u8; 12];
+        let read_bytes = read_up_to(input, &mut block)?;
+        block.truncate(read_bytes);
+        if read_bytes < 12 {
+            return Ok(Err(block));
+        }
+        let extra_len = u16::from_le_bytes([block[10], block[11]]) as usize;
+        block.resize(12 + extra_len, 0);
+        let read_bytes = read_up_to(input, &mut block[12..])?;
+        block.truncate(12 + read_bytes);
+        let Some((header_len, block_size)) = bgzf_header_len(&block) else {
+            return Ok(Err(block));
+        };
+        if block_size < header_len {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "invalid BGZF block size",
+            ));
+        }
+        block.resize(block_size, 0);
+        input.read_exact(&mut block[header_len..])?;
+        Ok(Ok(block))
+    }
+
+    fn decompress_block(block: &[u8], decompressed: &mut Vec<u8>) -> io::Result<()> {
+        // The decoder checks the CRC-32 and the size of every block
+        GzDecoder::new(block).read_to_end(decompressed)?;
+        Ok(())
+    }
+
+    fn serial<'r, R: Read + 'r>(rest: Rewound<R>) -> io::Result<Box<dyn Read + 'r>> {
+        Ok(Box::new(MultiGzDecoder::new(rest)))
+    }
+}
+
+/// The frames of the ZSTD files, such as those of pzstd. The frames larger than
+/// `MAX_PARALLEL_FRAME_SIZE` and the rest of the file after them are decompressed by a
+/// single thread.
+#[cfg(feature = "zstd")]
+struct ZstdFrames;
+
+#[cfg(feature = "zstd")]
+impl ZstdFrames {
+    /// Reads some more bytes of a frame, failing if the stream ends before.
+    fn read_more<R: Read>(input: &mut R, frame: &mut Vec<u8>, len: usize) -> io::Result<()> {
+        let start = frame.len();
+        frame.resize(start + len, 0);
+        input.read_exact(&mut frame[start..])
+    }
+}
+
+#[cfg(feature = "zstd")]
+impl Blocks for ZstdFrames {
+    const PER_THREAD: usize = 1;
+
+    fn read_block<R: Read>(input: &mut R) -> io::Result<Result<Vec<u8>, Vec<u8>>> {
+        let mut frame = vec![0u8; 4];
+        let read_bytes = read_up_to(input, &mut frame)?;
+        frame.truncate(read_bytes);
+        let Ok(magic) = <[u8; 4]>::try_from(frame.as_slice()) else {
+            return Ok(Err(frame));
+        };
+        let magic = u32::from_le_bytes(magic);
+        // Skippable frames, holding metadata
+        if magic & 0xffff_fff0 == 0x184d_2a50 {
+            Self::read_more(input, &mut frame, 4)?;
+            let size = u32::from_le_bytes([frame[4], frame[5], frame[6], frame[7]]) as usize;
+            if size > MAX_PARALLEL_FRAME_SIZE {
+                return Ok(Err(frame));
+            }
+            Self::read_more(input, &mut frame, size)?;
+            return Ok(Ok(frame));
+        }
+        if magic != 0xfd2f_b528 {
+            return Ok(Err(frame));
+        }
+
+        // The frame header, after its descriptor (RFC 8878)
+        Self::read_more(input, &mut frame, 1)?;
+        let descriptor = frame[4];
+        let single_segment = descriptor & 0x20 != 0;
+        let has_checksum = descriptor & 0x04 != 0;
+        let window_len = usize::from(!single_segment);
+        let dictionary_id_len = [0, 1, 2, 4][usize::from(descriptor & 0x03)];
+        let content_size_len = match descriptor >> 6 {
+            0 => usize::from(single_segment),
+            flag => 1 << flag,
+        };
+        Self::read_more(
+            input,
+            &mut frame,
+            window_len + dictionary_id_len + content_size_len,
+        )?;
+        loop {
+            let header_start = frame.len();
+            Self::read_more(input, &mut frame, 3)?;
+            let header = &frame[header_start..];
+            let header = u32::from_le_bytes([header[0], header[1], header[2], 0]);
+            let block_size = (header >> 3) as usize;
+            // RLE blocks hold a single byte repeated
+            let content_len = match (header >> 1) & 0x03 {
+                1 => 1,
+                _ => block_size,
+            };
+            Self::read_more(input, &mut frame, content_len)?;
+            if header & 0x01 != 0 {
+                break;
+            }
+            if frame.len() > MAX_PARALLEL_FRAME_SIZE {
+                return Ok(Err(frame));
+            }
+        }
+        if has_checksum {
+            Self::read_more(input, &mut frame, 4)?;
+        }
+        Ok(Ok(frame))
+    }
+
+    fn decompress_block(block: &[u8], decompressed: &mut Vec<u8>) -> io::Result<()> {
+        // The decoder checks the checksum of the frame, if any
+        zstd::stream::read::Decoder::with_buffer(block)?.read_to_end(decompressed)?;
+        Ok(())
+    }
+
+    fn serial<'r, R: Read + 'r>(rest: Rewound<R>) -> io::Result<Box<dyn Read + 'r>> {
+        Ok(Box::new(zstd::stream::read::Decoder::new(rest)?))
+    }
+}
+
+/// A decoder of the files made of independent blocks, decompressing them in parallel, see
+/// `open_gzip` and `open_zstd`.
+///
+/// The rest of a file is decompressed by a single thread from the first bytes which are not
+/// a block to decompress in parallel (see `Blocks::read_block`).
+struct ParallelDecoder<'r, R, B> {
+    /// The compressed stream, taken over by `serial` after the last block
+    input: Option<R>,
+    threads: usize,
+    /// The next batch of blocks, read while the previous one was decompressed
+    next: Option<io::Result<BlockBatch>>,
+    /// The decompressed content of the last batch of blocks, not read yet
+    decompressed: io::Cursor<Vec<u8>>,
+    serial: Option<Box<dyn Read + 'r>>,
+    blocks: PhantomData<B>,
+}
+
+/// A batch of blocks read from a stream, see `ParallelDecoder::read_batch`.
+struct BlockBatch {
+    blocks: Vec<Vec<u8>>,
+    /// The bytes read after the last block, if the batch ends the blocks
+    rest: Option<Vec<u8>>,
+}
+
+impl<'r, R: Read + 'r, B: Blocks> ParallelDecoder<'r, R, B> {
+    fn new(input: R, threads: usize) -> Self {
+        ParallelDecoder {
+            input: Some(input),
+            threads,
+            next: None,
+            decompressed: io::Cursor::new(Vec::new()),
+            serial: None,
+            blocks: PhantomData,
+        }
+    }
+
+    /// Reads the blocks the threads decompress at once, up to `Blocks::PER_THREAD` each.
+    fn read_batch(input: &mut R, threads: usize) -> io::Result<BlockBatch> {
+        let mut blocks = Vec::new();
+        while blocks.len() < threads * B::PER_THREAD {
+            match B::read_block(input)? {
+                Ok(block) => blocks.push(block),
+                Err(bytes) => {
+                    return Ok(BlockBatch {
+                        blocks,
+                        rest: Some(bytes),
+                    })
+                }
+            }
+        }
+        Ok(BlockBatch { blocks, rest: None })
+    }
+
+    /// Decompresses the next batch of blocks in parallel, reading the batch after it
+    /// meanwhile.
+    ///
+    /// # Returns
+    /// Whether the end of the blocks was reached
+    fn decompress_blocks(&mut self) -> io::Result<bool> {
+        let threads = self.threads;
+        let Some(input) = self.input.as_mut() else {
+            return Ok(true);
+        };
+        let batch = match self.next.take() {
+            Some(batch) => batch?,
+            None => Self::read_batch(input, threads)?,
+        };
+
+        let chunk_size = batch.blocks.len().div_ceil(threads).max(1);
+        let (decompressed, next) = thread::scope(|scope| {
+            let workers: Vec<_> = batch
+                .blocks
+                .chunks(chunk_size)
+                .map(|chunk| {
+                    scope.spawn(move || {
+                        let mut decompressed = Vec::new();
+                        for block in chunk {
+                            B::decompress_block(block, &mut decompressed)?;
+                        }
+                        Ok::<_, io::Error>(decompressed)
+                    })
+                })
+                .collect();
+            // Its errors are only returned once the content decompressed before is read
+            let next = batch
+                .rest
+                .is_none()
+                .then(|| Self::read_batch(input, threads));
+            let decompressed = workers
+                .into_iter()
+                .map(|worker| {
+                    worker
+                        .join()
+                        .unwrap_or_else(|panic| std::panic::resume_unwind(panic))
+                })
+                .collect::<io::Result<Vec<_>>>();
+            (decompressed, next)
+        });
+        self.decompressed = io::Cursor::new(decompressed?.concat());
+        self.next = next;
+
+        if let Some(bytes) = batch.rest {
+            let input = self
+                .input
+                .take()
+                .expect("the input is read until the last block");
+            if !bytes.is_empty() {
+                self.serial = Some(B::serial(io::Cursor::new(bytes).chain(input))?);
+            }
+        }
+        Ok(false)
+    }
+}
+
+impl<'r, R: Read + 'r, B: Blocks> Read for ParallelDecoder<'r, R, B> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read_bytes = self.decompressed.read(buf)?;
+            if read_bytes > 0 || buf.is_empty() {
+                return Ok(read_bytes);
+            }
+            if let Some(serial) = self.serial.as_mut() {
+                return serial.read(buf);
+            }
+            if self.decompress_blocks()? {
+                return Ok(0);
+            }
+        }
+    }
+}
+
+/// A stream whose first bytes were read and put back in front of it.
+pub type Rewound<R> = io::Chain<io::Cursor<Vec<u8>>, R>;
+
+/// Tells whether a decompressed stream holds a TAR archive, from its name or, when the
+/// name does not tell (e.g. for the standard input), from its first bytes.
+///
+/// # Arguments
+/// * `file_name` - Name of the decompressed file (see `decompressed_name`)
+/// * `reader` - A reader providing the decompressed content
+///
+/// # Returns
+/// * `Ok((bool, reader))` - Whether the stream is a TAR archive, and the stream with
+///   the bytes read to find out put back in front of it
+/// * `Err(io::Error)` - If the stream could not be read
+pub fn sniff_tar<R>(file_name: &str, mut reader: R) -> io::Result<(bool, Rewound<R>)>
+where
+    R: Read,
+{
+    let (is_tar, magic_bytes) = match file_name.ends_with(".tar") {
+        true => (true, Vec::new()),
+        false => {
+            let mut magic_bytes = vec![0u8; MAGIC_BYTES_SIZE];
+            let read_bytes = read_up_to(&mut reader, &mut magic_bytes)?;
+            magic_bytes.truncate(read_bytes);
+            let is_tar = detect_mime_type(&magic_bytes) == "application/x-tar";
+            (is_tar, magic_bytes)
+        }
+    };
+    match is_tar {
+        true => debug!(
+            file = file_name,
+            "the decompressed content is a TAR archive"
+        ),
+        false => debug!(
+            file = file_name,
+            "the decompressed content is a single file"
+        ),
+    }
+    Ok((is_tar, io::Cursor::new(magic_bytes).chain(reader)))
+}
+
+/// Applies a handler function to each entry of a TAR archive, skipping the directories
+/// unless `Options::include_dirs` tells otherwise.
+///
+/// # Arguments
+/// * `archive` - The TAR archive
+/// * `options` - The settings of the reading
+/// * `handler` - A function that processes each entry (e.g., displaying content or info)
+///
+/// # Returns
+/// * `Ok(())` if all operations succeeded
+/// * `Err(E)` if the archive could not be read or if the handler failed
+pub fn for_each_tar_entry<R, F, E>(
+    mut archive: tar::Archive<R>,
+    options: &dyn Options,
+    mut handler: F,
+) -> Result<(), E>
+where
+    R: Read,
+    F: FnMut(tar::Entry<R>) -> Result<(), E>,
+    E: From<io::Error>,
+{
+    let include_dirs = options.include_dirs();
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let entry_header = entry.header();
+
+        if entry_header.entry_type().is_dir() && !include_dirs {
+            debug!(
+                entry = %String::from_utf8_lossy(&entry.path_bytes()),
+                "skipped the directory, see --dirs"
+            );
+            continue;
+        }
+
+        options.on_entry();
+        handler(entry)?;
+    }
+    Ok(())
+}
+
+/// Returns the metadata of an entry of a TAR archive.
+///
+/// The paths which are not valid UTF-8, e.g. in the archives created with a legacy encoding,
+/// are read lossily (see `Entry::lossy_name`).
+fn tar_entry<R: Read>(entry: &tar::Entry<R>) -> io::Result<Entry> {
+    let header = entry.header();
+    let path = entry.path()?;
+    let file_type = match header.entry_type() {
+        tar::EntryType::Directory => S_IFDIR,
+        tar::EntryType::Symlink => S_IFLNK,
+        _ => S_IFREG,
+    };
+    let link_target = match header.entry_type() {
+        tar::EntryType::Symlink | tar::EntryType::Link => entry
+            .link_name()
+            .ok()
+            .flatten()
+            .map(|target| target.to_string_lossy().into_owned()),
+        _ => None,
+    };
+    let mut name = path.to_string_lossy().into_owned();
+    if file_type == S_IFDIR && !name.ends_with('/') {
+        name.push('/');
+    }
+    Ok(Entry {
+        lossy_name: path.to_str().is_none(),
+        name,
+        size: Some(header.size()?),
+        modified: header
+            .mtime()
+            .ok()
+            .and_then(|mtime| DateTime::from_timestamp(mtime as i64, 0)),
+        mode: header.mode().ok().map(|mode| file_type | (mode & 0o7777)),
+        uid: header.uid().ok(),
+        gid: header.gid().ok(),
+        user: header.username().ok().flatten().map(str::to_owned),
+        group: header.groupname().ok().flatten().map(str::to_owned),
+        link_target,
+        ..Default::default()
+    })
+}
+
+/// Tells whether a ZIP entry is encrypted and cannot be decrypted, for lack of a password
+/// (see `Options::password`).
+pub fn zip_entry_locked(file: &zip::read::ZipFile, options: &dyn Options) -> bool {
+    file.encrypted() && options.password().is_none()
+}
+
+/// Returns the metadata of an entry of a ZIP archive.
+///
+/// # Arguments
+/// * `file` - The ZIP entry
+/// * `options` - The settings of the reading, telling whether the entry can be decrypted
+fn zip_entry(file: &zip::read::ZipFile, options: &dyn Options) -> Entry {
+    Entry {
+        name: file.name().to_owned(),
+        size: Some(file.size()),
+        modified: file.last_modified().and_then(zip_datetime_to_utc),
+        mode: file.unix_mode(),
+        compression: Some(file.compression().to_string()),
+        compressed_size: Some(file.compressed_size()),
+        // AE-2 entries do not record their CRC-32, their integrity is checked by the ZIP reader
+        crc32: match zip_aes_extra_field(file) {
+            Some((2, _)) => None,
+            _ => Some(file.crc32()),
+        },
+        comment: Some(file.comment().to_owned()).filter(|comment| !comment.is_empty()),
+        encryption: zip_encryption(file),
+        locked: zip_entry_locked(file, options),
+        ..Default::default()
+    }
+}
+
+/// Determines the encryption method of a ZIP entry.
+///
+/// WinZip AES entries are identified by their AES extra field (see `zip_aes_extra_field`),
+/// any other encrypted entry uses the legacy ZipCrypto encryption.
+///
+/// # Arguments
+/// * `file` - The ZIP entry to check
+///
+/// # Returns
+/// * `Some(&str)` - The name of the encryption method (e.g. `ZipCrypto`, `AES-256 (AE-2)`)
+/// * `None` - If the entry is not encrypted
+fn zip_encryption(file: &zip::read::ZipFile) -> Option<&'static str> {
+    if !file.encrypted() {
+        return None;
+    }
+
+    Some(match zip_aes_extra_field(file) {
+        Some((1, 0x01)) => "AES-128 (AE-1)",
+        Some((1, 0x02)) => "AES-192 (AE-1)",
+        Some((1, 0x03)) => "AES-256 (AE-1)",
+        Some((2, 0x01)) => "AES-128 (AE-2)",
+        Some((2, 0x02)) => "AES-192 (AE-2)",
+        Some((2, 0x03)) => "AES-256 (AE-2)",
+        Some(_) => "AES",
+        None => "ZipCrypto",
+    })
+}
+
+/// Reads the WinZip AES extra field (header ID `0x9901`) of a ZIP entry.
+///
+/// AE-1 entries record the CRC-32 of their content while AE-2 entries, whose CRC-32 is
+/// always 0, only rely on the authentication code of the AES encryption.
+///
+/// # Arguments
+/// * `file` - The ZIP entry to read the extra field of
+///
+/// # Returns
+/// * `Some((u16, u8))` - The vendor version (1 for AE-1, 2 for AE-2) and the key strength
+///   (1 for 128 bits, 2 for 192 bits, 3 for 256 bits)
+/// * `None` - If the entry has no AES extra field
+fn zip_aes_extra_field(file: &zip::read::ZipFile) -> Option<(u16, u8)> {
+    let mut extra_data = file.extra_data().unwrap_or_default();
+    while extra_data.len() >= 4 {
+        let header_id = u16::from_le_bytes([extra_data[0], extra_data[1]]);
+        let size = u16::from_le_bytes([extra_data[2], extra_data[3]]) as usize;
+        let data = &extra_data[4..(4 + size).min(extra_data.len())];
+        // AES extra field: version (2 bytes), vendor ID (2 bytes), strength (1 byte), method (2 bytes)
+        if header_id == 0x9901 && data.len() >= 5 {
+            return Some((u16::from_le_bytes([data[0], data[1]]), data[4]));
+        }
+        extra_data = &extra_data[(4 + size).min(extra_data.len())..];
+    }
+    None
+}
+
+/// Converts a ZIP (MS-DOS) timestamp into a UTC date.
+///
+/// ZIP timestamps do not carry any timezone information, so they are
+/// interpreted in the local timezone, like `unzip` does.
+///
+/// # Arguments
+/// * `datetime` - The timestamp stored in the ZIP archive
+///
+/// # Returns
+/// * `Some(DateTime<Utc>)` - If the timestamp is a valid date
+/// * `None` - Otherwise
+fn zip_datetime_to_utc(datetime: zip::DateTime) -> Option<DateTime<Utc>> {
+    let naive = NaiveDate::from_ymd_opt(
+        datetime.year().into(),
+        datetime.month().into(),
+        datetime.day().into(),
+    )?
+    .and_hms_opt(
+        datetime.hour().into(),
+        datetime.minute().into(),
+        datetime.second().into(),
+    )?;
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .map(|local| local.with_timezone(&Utc))
+}
+
+/// Applies a handler function to each entry of a ZIP archive, skipping the directories
+/// unless `Options::include_dirs` tells otherwise.
+///
+/// Encrypted entries are decrypted with the password of the options. They are handed over
+/// without being decrypted when there is none (see `zip_entry_locked`), reading their
+/// content failing then.
+///
+/// # Arguments
+/// * `archive` - The ZIP archive
+/// * `options` - The settings of the reading
+/// * `handler` - A function that takes a `ZipFile` and processes it (e.g., displaying content or info)
+///
+/// # Returns
+/// * `Ok(())` if all operations succeeded
+/// * `Err(E)` if the entries could not be read or if the handler failed
+pub fn for_each_zip_entry<R, F, E>(
+    archive: &mut zip::read::ZipArchive<R>,
+    options: &dyn Options,
+    mut handler: F,
+) -> Result<(), E>
+where
+    R: Read + Seek,
+    F: FnMut(zip::read::ZipFile) -> Result<(), E>,
+    E: From<zip::result::ZipError>,
+{
+    let include_dirs = options.include_dirs();
+    for i in 0..archive.len() {
+        let locked = {
+            let file = archive.by_index_raw(i)?;
+            file.encrypted().then(|| zip_entry_locked(&file, options))
+        };
+        let file = match locked {
+            None => archive.by_index(i)?,
+            Some(true) => archive.by_index_raw(i)?,
+            Some(false) => {
+                archive.by_index_decrypt(i, options.password().unwrap_or_default().as_bytes())?
+            }
+        };
+        if file.is_dir() && !include_dirs {
+            debug!(entry = file.name(), "skipped the directory, see --dirs");
+            continue;
+        }
+        options.on_entry();
+        handler(file)?;
+    }
+    Ok(())
+}
+
+/// The size of the fixed part of the local header of the entries of ZIP archives.
+const ZIP_LOCAL_HEADER_SIZE: usize = 30;
+
+/// The first bytes of the local header of the entries of ZIP archives.
+const ZIP_LOCAL_HEADER_SIGNATURE: [u8; 4] = [0x50, 0x4b, 0x03, 0x04];
+
+/// Applies a handler function to each entry of a ZIP archive read from a stream.
+///
+/// Unlike `for_each_zip_entry`, the central directory at the end of the archive is not
+/// needed: the local headers are parsed one after the other, so that the archives of
+/// non-seekable inputs (e.g. the standard input) can be read. The price is that the entries
+/// written with a data descriptor (as streaming ZIP writers do) cannot be read, that the
+/// permissions of the entries are unknown and that encrypted entries cannot be decrypted.
+///
+/// # Arguments
+/// * `reader` - A reader providing the ZIP archive from its start
+/// * `options` - The settings of the reading
+/// * `handler` - A function that takes a `ZipFile` and processes it (e.g., displaying content or info)
+///
+/// # Returns
+/// * `Ok(())` if all operations succeeded
+/// * `Err(E)` if a local header is invalid, an entry uses a data descriptor or must be
+///   decrypted, or if the handler failed
+pub fn for_each_streamed_zip_entry<R, F, E>(
+    mut reader: R,
+    options: &dyn Options,
+    mut handler: F,
+) -> Result<(), E>
+where
+    R: Read,
+    F: FnMut(zip::read::ZipFile) -> Result<(), E>,
+    E: From<zip::result::ZipError>,
+{
+    let include_dirs = options.include_dirs();
+    debug!("reading the ZIP archive entry by entry, as the input cannot be seeked");
+    loop {
+        let mut header = [0u8; ZIP_LOCAL_HEADER_SIZE];
+        let read_bytes =
+            read_up_to(&mut reader, &mut header).map_err(zip::result::ZipError::from)?;
+        // The sizes of the entries written with a data descriptor follow their content, as
+        // told by the bit 3 of the flags of their local header
+        if read_bytes == ZIP_LOCAL_HEADER_SIZE
+            && header[..4] == ZIP_LOCAL_HEADER_SIGNATURE
+            && u16::from_le_bytes([header[6], header[7]]) & 0x08 != 0
+        {
+            return Err(zip::result::ZipError::UnsupportedArchive(
+                "entries written with a data descriptor cannot be read from a stream, \
+                save the archive to a file first",
+            )
+            .into());
+        }
+        let mut reader = io::Cursor::new(&header[..read_bytes]).chain(&mut reader);
+        let file = match zip::read::read_zipfile_from_stream(&mut reader)? {
+            Some(file) => file,
+            None => return Ok(()),
+        };
+        if file.encrypted() && options.password().is_some() {
+            return Err(zip::result::ZipError::UnsupportedArchive(
+                "encrypted entries cannot be decrypted when the archive is streamed",
+            )
+            .into());
+        }
+        if file.is_dir() && !include_dirs {
+            debug!(entry = file.name(), "skipped the directory, see --dirs");
+            continue;
+        }
+        options.on_entry();
+        handler(file)?;
+    }
+}
+
+/// A reader which can also be seeked, as the inputs of the formats are.
+pub trait ReadSeek: Read + Seek {}
+
+impl<T: Read + Seek + ?Sized> ReadSeek for T {}
+
+/// The mask of the bits of a Unix mode telling the type of a file, see `Entry::mode`.
+pub const S_IFMT: u32 = 0o170000;
+/// The type bits of the Unix mode of a directory.
+pub const S_IFDIR: u32 = 0o040000;
+/// The type bits of the Unix mode of a regular file.
+pub const S_IFREG: u32 = 0o100000;
+/// The type bits of the Unix mode of a symbolic link.
+pub const S_IFLNK: u32 = 0o120000;
+
+/// The metadata of a file of an input, as recorded by its format, see `Format::list_entries`.
+///
+/// Only the name is always known, the formats filling in what they record.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Entry {
+    /// Path of the file in the input, ending with a slash for the directories
+    pub name: String,
+    /// Decompressed size, `None` when it is only known once the content is read (e.g. the
+    /// file of a GZIP file)
+    pub size: Option<u64>,
+    pub modified: Option<DateTime<Utc>>,
+    /// Unix mode, including the file type bits (see `S_IFMT`)
+    pub mode: Option<u32>,
+    pub uid: Option<u64>,
+    pub gid: Option<u64>,
+    pub user: Option<String>,
+    pub group: Option<String>,
+    /// Compression method, for the archives compressing each file separately
+    pub compression: Option<String>,
+    pub compressed_size: Option<u64>,
+    /// CRC-32 of the content recorded in the archive, to be checked once it is read
+    pub crc32: Option<u32>,
+    pub comment: Option<String>,
+    /// Operating system on which the file was compressed
+    pub system: Option<&'static str>,
+    /// Encryption method, when the file is encrypted
+    pub encryption: Option<&'static str>,
+    /// Whether the file is encrypted and cannot be decrypted (see `zip_entry_locked`), its
+    /// content being unreadable
+    pub locked: bool,
+    /// Target of the file when it is a symbolic or a hard link
+    pub link_target: Option<String>,
+    /// Whether the path is not valid UTF-8, its invalid bytes being replaced by U+FFFD
+    pub lossy_name: bool,
+}
+
+/// A function processing the content of every file of an input, see `Format::for_each_entry`.
+pub type EntryHandler<'h> = dyn FnMut(Option<&str>, &mut dyn Read) -> Result<(), Error> + 'h;
+
+/// A function processing the metadata and the content of every file of an input, see
+/// `Format::list_entries`.
+pub type ListingHandler<'h> = dyn FnMut(&Entry, &mut dyn Read) -> Result<(), Error> + 'h;
+
+/// A function processing the content of a single file of an input, see `Format::open_entry`.
+pub type ContentHandler<'h> = dyn FnMut(&mut dyn Read) -> Result<(), Error> + 'h;
+
+/// A format of compressed files or archives, which a `Registry` hands the inputs over to.
+///
+/// Implementing it adds the support of a new format, e.g. an in-house archive format,
+/// without changing how the inputs are dispatched.
+pub trait Format: Send + Sync {
+    /// Returns the MIME type designating the format, e.g. `application/zip`.
+    fn mime_type(&self) -> &'static str;
+
+    /// Returns the short name of the format, e.g. `zip`. By default, its MIME type.
+    fn name(&self) -> &str {
+        self.mime_type()
+    }
+
+    /// Describes how the inputs in this format are read, for the diagnostics, e.g. `ZIP
+    /// archive reader`.
+    fn decoder(&self) -> &str {
+        "reader of the registry"
+    }
+
+    /// Tells whether some content is in this format, from its first bytes (up to
+    /// `MAGIC_BYTES_SIZE`). By default, whether `infer` detects the MIME type of the format.
+    fn sniff(&self, magic_bytes: &[u8]) -> bool {
+        infer::get(magic_bytes).is_some_and(|file_type| file_type.mime_type() == self.mime_type())
+    }
+
+    /// Tells whether an input is in this format from its name, for the formats whose first
+    /// bytes do not tell (see `Registry::detect_name`). By default, never.
+    fn matches_name(&self, name: &str) -> bool {
+        let _ = name;
+        false
+    }
+
+    /// Tells whether the format archives files rather than compressing a single stream,
+    /// in which case `decompress` returns `None`. By default, `true`.
+    fn is_archive(&self) -> bool {
+        true
+    }
+
+    /// Returns the decompressed content of an input, for the formats compressing a single
+    /// stream (e.g. GZIP) rather than archiving files. By default, `None`: the format is an
+    /// archive.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the input, without its directory, used to name the decompressed file
+    /// * `input` - The raw content of the input, from its start
+    /// * `compressed_size` - The size of the input, `None` when unknown (e.g. for the
+    ///   standard input)
+    /// * `options` - The settings of the reading
+    ///
+    /// # Returns
+    /// * `Some(Ok((String, reader)))` - The name of the decompressed file, and its content
+    ///   wrapped with `Options::wrap`
+    /// * `Some(Err(io::Error))` - If the input could not be read
+    fn decompress<'r>(
+        &self,
+        name: &str,
+        input: Box<dyn Read + 'r>,
+        compressed_size: Option<u64>,
+        options: &'r dyn Options,
+    ) -> Option<io::Result<(String, Box<dyn Read + 'r>)>> {
+        let _ = (name, input, compressed_size, options);
+        None
+    }
+
+    /// Applies a handler function to every file of an input in this format.
+    ///
+    /// The handler receives the name of each file along with a reader providing its
+    /// decompressed content, to be wrapped with `Options::wrap`. Its errors must be
+    /// returned as is.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the input, without its directory, used to name the decompressed files
+    /// * `input` - The raw content of the input, from its start
+    /// * `options` - The settings of the reading
+    /// * `handler` - A function processing the content of every file
+    fn for_each_entry(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut EntryHandler,
+    ) -> Result<(), Error>;
+
+    /// Applies a handler function to the metadata of every file of an input in this format,
+    /// along with a reader providing its decompressed content, which the handler may leave
+    /// unread. The content of the locked files (see `Entry::locked`) cannot be read.
+    ///
+    /// By default, only the names given by `for_each_entry` are known.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the input, without its directory, used to name the decompressed files
+    /// * `input` - The raw content of the input, from its start
+    /// * `options` - The settings of the reading
+    /// * `handler` - A function processing the metadata and the content of every file
+    ///
+    /// # Returns
+    /// * `Ok(Option<String>)` - The comment of the whole input, if it has one
+    /// * `Err(Error)` - If the input could not be read or if the handler failed
+    fn list_entries(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut ListingHandler,
+    ) -> Result<Option<String>, Error> {
+        self.for_each_entry(name, input, options, &mut |name, content| {
+            let entry = Entry {
+                name: name.unwrap_or_default().to_owned(),
+                ..Default::default()
+            };
+            handler(&entry, content)
+        })?;
+        Ok(None)
+    }
+
+    /// Applies a handler function to the decompressed content of a single file of an input
+    /// in this format, found by its path.
+    ///
+    /// By default, the files are gone through with `for_each_entry`, the first one having
+    /// the path being handed over.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the input, without its directory, used to name the decompressed files
+    /// * `input` - The raw content of the input, from its start
+    /// * `options` - The settings of the reading
+    /// * `path` - The path of the file, as handed over by `for_each_entry`
+    /// * `handler` - A function processing the content of the file
+    ///
+    /// # Returns
+    /// * `Ok(true)` - If the file was found and handled
+    /// * `Ok(false)` - If the input holds no file with this path
+    /// * `Err(Error)` - If the input could not be read or if the handler failed
+    fn open_entry(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        path: &str,
+        handler: &mut ContentHandler,
+    ) -> Result<bool, Error> {
+        find_entry(self, name, input, options, path, handler)
+    }
+}
+
+/// Applies a handler function to the content of the first file of an input having a path,
+/// going through its files with `Format::for_each_entry`, see `Format::open_entry`.
+fn find_entry<F>(
+    format: &F,
+    name: &str,
+    input: &mut dyn ReadSeek,
+    options: &dyn Options,
+    path: &str,
+    handler: &mut ContentHandler,
+) -> Result<bool, Error>
+where
+    F: Format + ?Sized,
+{
+    let mut found = false;
+    format.for_each_entry(name, input, options, &mut |name, content| {
+        if found || name != Some(path) {
+            return Ok(());
+        }
+        found = true;
+        handler(content)
+    })?;
+    Ok(found)
+}
+
+/// ZIP archives, read entry by entry when they cannot be seeked (see
+/// `for_each_streamed_zip_entry`).
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Zip;
+
+impl Format for Zip {
+    fn mime_type(&self) -> &'static str {
+        "application/zip"
+    }
+
+    fn name(&self) -> &str {
+        "zip"
+    }
+
+    fn decoder(&self) -> &str {
+        "ZIP archive reader"
+    }
+
+    fn for_each_entry(
+        &self,
+        _name: &str,
+        mut input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut EntryHandler,
+    ) -> Result<(), Error> {
+        let handle_file = |file: zip::read::ZipFile| {
+            if zip_entry_locked(&file, options) {
+                return Err(zip::result::ZipError::UnsupportedArchive(
+                    zip::result::ZipError::PASSWORD_REQUIRED,
+                )
+                .into());
+            }
+            let name = file.name().to_owned();
+            let compressed_size = Some(file.compressed_size());
+            handler(
+                Some(&name),
+                &mut options.wrap(Box::new(file), compressed_size),
+            )
+        };
+        if !is_seekable(&mut input) {
+            return for_each_streamed_zip_entry(input, options, handle_file);
+        }
+        let mut archive = zip::read::ZipArchive::new(input)?;
+        for_each_zip_entry(&mut archive, options, handle_file)
+    }
+
+    fn list_entries(
+        &self,
+        _name: &str,
+        mut input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut ListingHandler,
+    ) -> Result<Option<String>, Error> {
+        let list_file = |mut file: zip::read::ZipFile| {
+            let mut entry = zip_entry(&file, options);
+            // The target of a symbolic link is stored as its content, in any encoding
+            if !entry.locked && entry.mode.is_some_and(|mode| mode & S_IFMT == S_IFLNK) {
+                let mut target = Vec::new();
+                (&mut file).take(4096).read_to_end(&mut target)?;
+                entry.link_target = Some(String::from_utf8_lossy(&target).into_owned());
+                return handler(&entry, &mut io::Cursor::new(target));
+            }
+            let compressed_size = Some(file.compressed_size());
+            handler(&entry, &mut options.wrap(Box::new(file), compressed_size))
+        };
+        if !is_seekable(&mut input) {
+            // The comment of the archive is stored in its central directory, which is not read
+            for_each_streamed_zip_entry(input, options, list_file)?;
+            return Ok(None);
+        }
+        let mut archive = zip::read::ZipArchive::new(input)?;
+        let comment = Some(String::from_utf8_lossy(archive.comment()).into_owned())
+            .filter(|comment| !comment.is_empty());
+        for_each_zip_entry(&mut archive, options, list_file)?;
+        Ok(comment)
+    }
+
+    fn open_entry(
+        &self,
+        name: &str,
+        mut input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        path: &str,
+        handler: &mut ContentHandler,
+    ) -> Result<bool, Error> {
+        if !is_seekable(&mut input) {
+            return find_entry(self, name, input, options, path, handler);
+        }
+        // The entry is found in the central directory, without reading the others
+        let mut archive = zip::read::ZipArchive::new(input)?;
+        let Some(index) = archive.index_for_name(path) else {
+            return Ok(false);
+        };
+        let encrypted = archive.by_index_raw(index)?.encrypted();
+        let file = match encrypted {
+            false => archive.by_index(index)?,
+            true => match options.password() {
+                Some(password) => archive.by_index_decrypt(index, password.as_bytes())?,
+                None => {
+                    return Err(zip::result::ZipError::UnsupportedArchive(
+                        zip::result::ZipError::PASSWORD_REQUIRED,
+                    )
+                    .into())
+                }
+            },
+        };
+        let compressed_size = Some(file.compressed_size());
+        handler(&mut options.wrap(Box::new(file), compressed_size))?;
+        Ok(true)
+    }
+}
+
+/// TAR archives.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Tar;
+
+impl Format for Tar {
+    fn mime_type(&self) -> &'static str {
+        "application/x-tar"
+    }
+
+    fn name(&self) -> &str {
+        "tar"
+    }
+
+    fn decoder(&self) -> &str {
+        "TAR archive reader"
+    }
+
+    fn for_each_entry(
+        &self,
+        _name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut EntryHandler,
+    ) -> Result<(), Error> {
+        for_each_tar_entry(tar::Archive::new(input), options, |mut entry| {
+            let path = entry.path()?.to_string_lossy().into_owned();
+            handler(Some(&path), &mut entry)
+        })
+    }
+
+    fn list_entries(
+        &self,
+        _name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut ListingHandler,
+    ) -> Result<Option<String>, Error> {
+        for_each_tar_entry(tar::Archive::new(input), options, |mut entry| {
+            handler(&tar_entry(&entry)?, &mut entry)
+        })?;
+        Ok(None)
+    }
+}
+
+/// GZIP files, holding a single file or a TAR archive. The decompressed file is named after
+/// the original name stored in the header when there is one.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Gzip;
+
+impl Format for Gzip {
+    fn mime_type(&self) -> &'static str {
+        "application/gzip"
+    }
+
+    fn name(&self) -> &str {
+        "gzip"
+    }
+
+    fn is_archive(&self) -> bool {
+        false
+    }
+
+    fn decoder(&self) -> &str {
+        "GZIP decoder"
+    }
+
+    fn decompress<'r>(
+        &self,
+        name: &str,
+        input: Box<dyn Read + 'r>,
+        compressed_size: Option<u64>,
+        options: &'r dyn Options,
+    ) -> Option<io::Result<(String, Box<dyn Read + 'r>)>> {
+        Some(
+            open_gzip(input, compressed_size, options).map(|(metadata, gz)| {
+                let file_name = metadata
+                    .file_name
+                    .unwrap_or_else(|| decompressed_name(name));
+                (file_name, options.wrap(gz, compressed_size))
+            }),
+        )
+    }
+
+    fn for_each_entry(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut EntryHandler,
+    ) -> Result<(), Error> {
+        for_each_decompressed_entry_of(self, name, input, options, handler)
+    }
+
+    fn list_entries(
+        &self,
+        name: &str,
+        mut input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut ListingHandler,
+    ) -> Result<Option<String>, Error> {
+        // The length of the standard input is unknown
+        let compressed_size = stream_len(&mut input).ok();
+        let (metadata, gz) = open_gzip(input, compressed_size, options)?;
+        // The header describes the compressed file, not the entries of a TAR archive
+        let file = Entry {
+            name: metadata
+                .file_name
+                .unwrap_or_else(|| decompressed_name(name)),
+            modified: metadata.modified,
+            system: metadata.system,
+            comment: metadata.comment,
+            ..Default::default()
+        };
+        let mut content = options.wrap(gz, compressed_size);
+        list_decompressed_entries(file, &mut content, options, handler)?;
+        Ok(None)
+    }
+}
+
+/// BZIP2 files, holding a single file or a TAR archive.
+#[cfg(feature = "bzip2")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Bzip2;
+
+#[cfg(feature = "bzip2")]
+impl Format for Bzip2 {
+    fn mime_type(&self) -> &'static str {
+        "application/x-bzip2"
+    }
+
+    fn name(&self) -> &str {
+        "bzip2"
+    }
+
+    fn is_archive(&self) -> bool {
+        false
+    }
+
+    fn decoder(&self) -> &str {
+        "BZIP2 decoder"
+    }
+
+    fn decompress<'r>(
+        &self,
+        name: &str,
+        input: Box<dyn Read + 'r>,
+        compressed_size: Option<u64>,
+        options: &'r dyn Options,
+    ) -> Option<io::Result<(String, Box<dyn Read + 'r>)>> {
+        let bz = bzip2::read::BzDecoder::new(input);
+        Some(Ok((
+            decompressed_name(name),
+            options.wrap(Box::new(bz), compressed_size),
+        )))
+    }
+
+    fn for_each_entry(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut EntryHandler,
+    ) -> Result<(), Error> {
+        for_each_decompressed_entry_of(self, name, input, options, handler)
+    }
+
+    fn list_entries(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut ListingHandler,
+    ) -> Result<Option<String>, Error> {
+        list_decompressed_entries_of(self, name, input, options, handler)?;
+        Ok(None)
+    }
+}
+
+/// XZ files, holding a single file or a TAR archive.
+#[cfg(feature = "xz")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Xz;
+
+#[cfg(feature = "xz")]
+impl Format for Xz {
+    fn mime_type(&self) -> &'static str {
+        "application/x-xz"
+    }
+
+    fn name(&self) -> &str {
+        "xz"
+    }
+
+    fn is_archive(&self) -> bool {
+        false
+    }
+
+    fn decoder(&self) -> &str {
+        "XZ decoder"
+    }
+
+    fn decompress<'r>(
+        &self,
+        name: &str,
+        input: Box<dyn Read + 'r>,
+        compressed_size: Option<u64>,
+        options: &'r dyn Options,
+    ) -> Option<io::Result<(String, Box<dyn Read + 'r>)>> {
+        Some(
+            open_xz(input, compressed_size, options)
+                .map(|xz| (decompressed_name(name), options.wrap(xz, compressed_size))),
+        )
+    }
+
+    fn for_each_entry(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut EntryHandler,
+    ) -> Result<(), Error> {
+        for_each_decompressed_entry_of(self, name, input, options, handler)
+    }
+
+    fn list_entries(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut ListingHandler,
+    ) -> Result<Option<String>, Error> {
+        list_decompressed_entries_of(self, name, input, options, handler)?;
+        Ok(None)
+    }
+}
+
+/// ZSTD files, holding a single file or a TAR archive.
+#[cfg(feature = "zstd")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Zstd;
+
+#[cfg(feature = "zstd")]
+impl Format for Zstd {
+    fn mime_type(&self) -> &'static str {
+        "application/zstd"
+    }
+
+    fn name(&self) -> &str {
+        "zstd"
+    }
+
+    fn is_archive(&self) -> bool {
+        false
+    }
+
+    fn decoder(&self) -> &str {
+        "ZSTD decoder"
+    }
+
+    fn decompress<'r>(
+        &self,
+        name: &str,
+        input: Box<dyn Read + 'r>,
+        compressed_size: Option<u64>,
+        options: &'r dyn Options,
+    ) -> Option<io::Result<(String, Box<dyn Read + 'r>)>> {
+        Some(
+            open_zstd(input, compressed_size, options)
+                .map(|zst| (decompressed_name(name), options.wrap(zst, compressed_size))),
+        )
+    }
+
+    fn for_each_entry(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut EntryHandler,
+    ) -> Result<(), Error> {
+        for_each_decompressed_entry_of(self, name, input, options, handler)
+    }
+
+    fn list_entries(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut ListingHandler,
+    ) -> Result<Option<String>, Error> {
+        list_decompressed_entries_of(self, name, input, options, handler)?;
+        Ok(None)
+    }
+}
+
+/// Decompresses an input with `Format::decompress`, then applies a handler function to the
+/// file it holds, or to every file of its TAR archive (see `for_each_decompressed_entry`).
+fn for_each_decompressed_entry_of<F>(
+    format: &F,
+    name: &str,
+    mut input: &mut dyn ReadSeek,
+    options: &dyn Options,
+    handler: &mut EntryHandler,
+) -> Result<(), Error>
+where
+    F: Format + ?Sized,
+{
+    // The length of the standard input is unknown
+    let compressed_size = stream_len(&mut input).ok();
+    let (file_name, mut content) = format
+        .decompress(name, Box::new(input), compressed_size, options)
+        .expect("the format compresses a single stream")?;
+    for_each_decompressed_entry(file_name, &mut content, options, handler)
+}
+
+/// Decompresses an input with `Format::decompress`, then applies a handler function to the
+/// metadata of the file it holds, or of every file of its TAR archive (see
+/// `list_decompressed_entries`).
+#[cfg(any(feature = "bzip2", feature = "xz", feature = "zstd"))]
+fn list_decompressed_entries_of<F>(
+    format: &F,
+    name: &str,
+    mut input: &mut dyn ReadSeek,
+    options: &dyn Options,
+    handler: &mut ListingHandler,
+) -> Result<(), Error>
+where
+    F: Format + ?Sized,
+{
+    // The length of the standard input is unknown
+    let compressed_size = stream_len(&mut input).ok();
+    let (file_name, mut content) = format
+        .decompress(name, Box::new(input), compressed_size, options)
+        .expect("the format compresses a single stream")?;
+    let file = Entry {
+        name: file_name,
+        ..Default::default()
+    };
+    list_decompressed_entries(file, &mut content, options, handler)
+}
+
+/// Applies a handler function to the file of a compressed file, or to every file of the TAR
+/// archive it holds (see `sniff_tar`).
+///
+/// # Arguments
+/// * `file_name` - Name of the decompressed file (see `decompressed_name`)
+/// * `reader` - A reader providing the decompressed content
+/// * `options` - The settings of the reading
+/// * `handler` - A function processing the content of every file
+pub fn for_each_decompressed_entry(
+    file_name: String,
+    reader: &mut dyn Read,
+    options: &dyn Options,
+    handler: &mut EntryHandler,
+) -> Result<(), Error> {
+    let (is_tar, mut reader) = sniff_tar(&file_name, reader)?;
+    if is_tar {
+        let archive = tar::Archive::new(&mut reader);
+        for_each_tar_entry(archive, options, |mut entry| {
+            let path = entry.path()?.to_string_lossy().into_owned();
+            handler(Some(&path), &mut entry)
+        })?;
+        // Reading up to the end of the stream makes the decoder check its trailer
+        io::copy(&mut reader, &mut io::sink())?;
+        Ok(())
+    } else {
+        handler(Some(&file_name), &mut reader)
+    }
+}
+
+/// Applies a handler function to the metadata of the file of a compressed file, or of every
+/// file of the TAR archive it holds (see `sniff_tar`), like `for_each_decompressed_entry`.
+///
+/// # Arguments
+/// * `file` - The metadata of the decompressed file, named after it (see `decompressed_name`)
+/// * `reader` - A reader providing the decompressed content
+/// * `options` - The settings of the reading
+/// * `handler` - A function processing the metadata and the content of every file
+pub fn list_decompressed_entries(
+    file: Entry,
+    reader: &mut dyn Read,
+    options: &dyn Options,
+    handler: &mut ListingHandler,
+) -> Result<(), Error> {
+    let (is_tar, mut reader) = sniff_tar(&file.name, reader)?;
+    if is_tar {
+        let archive = tar::Archive::new(&mut reader);
+        for_each_tar_entry(archive, options, |mut entry| {
+            handler(&tar_entry(&entry)?, &mut entry)
+        })?;
+        // Reading up to the end of the stream makes the decoder check its trailer
+        io::copy(&mut reader, &mut io::sink())?;
+        Ok(())
+    } else {
+        handler(&file, &mut reader)
+    }
+}
+
+/// Sets the errors of a handler which are not those of the library aside, replacing them by
+/// `Error::Handler` until they are returned, see `Registry::for_each_entry`.
+struct HandlerError<E>(Option<E>);
+
+impl<E: From<Error>> HandlerError<E> {
+    /// Sets the error of the handler aside, if it failed.
+    fn set_aside(&mut self, result: Result<(), E>) -> Result<(), Error> {
+        result.map_err(|err| {
+            self.0 = Some(err);
+            Error::Handler
+        })
+    }
+
+    /// Returns the result of a format, with the error of the handler when it failed.
+    fn returned<T>(self, result: Result<T, Error>) -> Result<T, E> {
+        match (result, self.0) {
+            (Err(_), Some(err)) => Err(err),
+            (result, _) => Ok(result?),
+        }
+    }
+}
+
+/// The formats zcatr can read the files of, which the inputs are dispatched to by their
+/// MIME type.
+///
+/// The default registry holds the formats built in zcatr: ZIP, TAR, GZIP, and BZIP2, XZ and
+/// ZSTD (with the `bzip2`, `xz` and `zstd` features).
+/// Formats registered later take precedence over the earlier ones, so that a built-in
+/// format can be replaced.
+pub struct Registry {
+    formats: Vec<Box<dyn Format>>,
+}
+
+impl Registry {
+    /// Creates a registry without any format.
+    pub fn new() -> Self {
+        Registry {
+            formats: Vec::new(),
+        }
+    }
+
+    /// Adds a format to the registry.
+    pub fn register(&mut self, format: impl Format + 'static) -> &mut Self {
+        self.formats.push(Box::new(format));
+        self
+    }
+
+    /// Returns the format designated by a MIME type, if registered.
+    pub fn get(&self, mime_type: &str) -> Option<&dyn Format> {
+        self.formats
+            .iter()
+            .rev()
+            .find(|format| format.mime_type() == mime_type)
+            .map(Box::as_ref)
+    }
+
+    /// Returns the registered formats, in the order they were registered.
+    pub fn formats(&self) -> impl Iterator<Item = &dyn Format> {
+        self.formats.iter().map(Box::as_ref)
+    }
+
+    /// Returns the format of some content from its first bytes, see `Format::sniff`.
+    pub fn detect(&self, magic_bytes: &[u8]) -> Option<&dyn Format> {
+        self.formats
+            .iter()
+            .rev()
+            .find(|format| format.sniff(magic_bytes))
+            .map(Box::as_ref)
+    }
+
+    /// Returns the format of an input from its name, see `Format::matches_name`. Only worth
+    /// asking when its first bytes tell nothing, as names can lie.
+    pub fn detect_name(&self, name: &str) -> Option<&dyn Format> {
+        self.formats
+            .iter()
+            .rev()
+            .find(|format| format.matches_name(name))
+            .map(Box::as_ref)
+    }
+
+    /// Applies a handler function to every file of an input, handing it over to the
+    /// registered format designated by its MIME type.
+    ///
+    /// The handler receives the name of each file inside the input along with a reader
+    /// providing its decompressed content. Inputs whose format is not registered are
+    /// handed over as a whole, without a name.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the input, without its directory, used to name the decompressed files
+    /// * `reader` - A reader providing the raw content of the input, e.g. a file or an
+    ///   archive nested in another one and loaded in memory
+    /// * `mime_type` - The MIME type of the input, see `detect_format`
+    /// * `options` - The settings of the reading
+    /// * `handler` - A function processing the content of every file
+    ///
+    /// # Returns
+    /// * `Ok(())` if all operations succeeded
+    /// * `Err(E)` if the input could not be read, an encrypted entry could not be decrypted,
+    ///   or if the handler failed
+    pub fn for_each_entry<R, F, E>(
+        &self,
+        name: &str,
+        mut reader: R,
+        mime_type: &str,
+        options: &dyn Options,
+        mut handler: F,
+    ) -> Result<(), E>
+    where
+        R: Read + Seek,
+        F: FnMut(Option<&str>, &mut dyn Read) -> Result<(), E>,
+        E: From<Error>,
+    {
+        let Some(format) = self.get(mime_type) else {
+            return handler(None, &mut reader);
+        };
+        // The formats only know the errors of the library, those of the handler are set
+        // aside and replaced by `Error::Handler` until they are returned
+        let mut handler_error = HandlerError(None);
+        let result = format.for_each_entry(name, &mut reader, options, &mut |name, content| {
+            handler_error.set_aside(handler(name, content))
+        });
+        handler_error.returned(result)
+    }
+
+    /// Applies a handler function to the metadata and the content of every file of an input,
+    /// handing it over to the registered format designated by its MIME type (see
+    /// `Format::list_entries`).
+    ///
+    /// Inputs whose format is not registered are handed over as a whole, as a file named
+    /// after the input whose other metadata is unknown.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the input, without its directory, used to name the decompressed files
+    /// * `reader` - A reader providing the raw content of the input
+    /// * `mime_type` - The MIME type of the input, see `detect_format`
+    /// * `options` - The settings of the reading
+    /// * `handler` - A function processing the metadata and the content of every file
+    ///
+    /// # Returns
+    /// * `Ok(Option<String>)` - The comment of the whole input, if it has one
+    /// * `Err(E)` - If the input could not be read or if the handler failed
+    pub fn list_entries<R, F, E>(
+        &self,
+        name: &str,
+        mut reader: R,
+        mime_type: &str,
+        options: &dyn Options,
+        mut handler: F,
+    ) -> Result<Option<String>, E>
+    where
+        R: Read + Seek,
+        F: FnMut(&Entry, &mut dyn Read) -> Result<(), E>,
+        E: From<Error>,
+    {
+        let Some(format) = self.get(mime_type) else {
+            let entry = Entry {
+                name: name.to_owned(),
+                ..Default::default()
+            };
+            return handler(&entry, &mut reader).map(|()| None);
+        };
+        let mut handler_error = HandlerError(None);
+        let result = format.list_entries(name, &mut reader, options, &mut |entry, content| {
+            handler_error.set_aside(handler(entry, content))
+        });
+        handler_error.returned(result)
+    }
+
+    /// Applies a handler function to the content of a single file of an input, found by its
+    /// path, handing the input over to the registered format designated by its MIME type
+    /// (see `Format::open_entry`).
+    ///
+    /// Inputs whose format is not registered are handed over as a whole, whatever the path.
+    ///
+    /// # Arguments
+    /// * `name` - Name of the input, without its directory, used to name the decompressed files
+    /// * `reader` - A reader providing the raw content of the input
+    /// * `mime_type` - The MIME type of the input, see `detect_format`
+    /// * `options` - The settings of the reading
+    /// * `path` - The path of the file, as handed over by `for_each_entry`
+    /// * `handler` - A function processing the content of the file
+    ///
+    /// # Returns
+    /// * `Ok(bool)` - Whether the file was found and handled
+    /// * `Err(E)` - If the input could not be read or if the handler failed
+    pub fn open_entry<R, F, E>(
+        &self,
+        name: &str,
+        mut reader: R,
+        mime_type: &str,
+        options: &dyn Options,
+        path: &str,
+        mut handler: F,
+    ) -> Result<bool, E>
+    where
+        R: Read + Seek,
+        F: FnMut(&mut dyn Read) -> Result<(), E>,
+        E: From<Error>,
+    {
+        let Some(format) = self.get(mime_type) else {
+            return handler(&mut reader).map(|()| true);
+        };
+        let mut handler_error = HandlerError(None);
+        let result = format.open_entry(name, &mut reader, options, path, &mut |content| {
+            handler_error.set_aside(handler(content))
+        });
+        handler_error.returned(result)
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list()
+            .entries(self.formats.iter().map(|format| format.mime_type()))
+            .finish()
+    }
+}
+
+impl Default for Registry {
+    fn default() -> Self {
+        let mut registry = Registry::new();
+        registry.register(Zip).register(Tar).register(Gzip);
+        #[cfg(feature = "bzip2")]
+        registry.register(Bzip2);
+        #[cfg(feature = "xz")]
+        registry.register(Xz);
+        #[cfg(feature = "zstd")]
+        registry.register(Zstd);
+        registry
+    }
+}
+
+/// Returns the registry of the formats built in zcatr.
+pub fn registry() -> &'static Registry {
+    static REGISTRY: OnceLock<Registry> = OnceLock::new();
+    REGISTRY.get_or_init(Registry::default)
+}
+
+/// Applies a handler function to every file of an input, whatever its format among those
+/// built in zcatr (see `Registry::for_each_entry`).
+pub fn for_each_entry<R, F, E>(
+    name: &str,
+    reader: R,
+    mime_type: &str,
+    options: &dyn Options,
+    handler: F,
+) -> Result<(), E>
+where
+    R: Read + Seek,
+    F: FnMut(Option<&str>, &mut dyn Read) -> Result<(), E>,
+    E: From<Error>,
+{
+    registry().for_each_entry(name, reader, mime_type, options, handler)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Cursor, Read, Write};
+
+    use flate2::{write::GzEncoder, Compression, GzBuilder};
+
+    use super::*;
+
+    /// Options decompressing the BGZF files with a number of threads.
+    struct Threads(usize);
+
+    impl Options for Threads {
+        fn threads(&self) -> usize {
+            self.0
+        }
+    }
+
+    /// Returns a BGZF block: a GZIP member whose `BC` subfield gives its size minus one.
+    fn bgzf_block(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzBuilder::new()
+            .extra(vec![b'B', b'C', 2, 0, 0, 0])
+            .write(Vec::new(), Compression::fast());
+        encoder.write_all(content).unwrap();
+        let mut block = encoder.finish().unwrap();
+        let size = (block.len() - 1) as u16;
+        block[16..18].copy_from_slice(&size.to_le_bytes());
+        block
+    }
+
+    fn gzip(content: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::fast());
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    fn decompress(threads: usize, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        let size = Some(PARALLEL_GZIP_THRESHOLD);
+        let (_, mut gz) = open_gzip(compressed, size, &Threads(threads))?;
+        let mut decompressed = Vec::new();
+        gz.read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    #[test]
+    fn test_decompressed_name() {
+        assert_eq!(decompressed_name("notes.txt.gz"), "notes.txt");
+        assert_eq!(decompressed_name("backup.tgz"), "backup.tar");
+        assert_eq!(decompressed_name("backup.tbz2"), "backup.tar");
+        assert_eq!(decompressed_name("backup.txz"), "backup.tar");
+        assert_eq!(decompressed_name("backup.tzst"), "backup.tar");
+        assert_eq!(decompressed_name("notes"), "notes");
+    }
+
+    #[test]
+    fn test_bgzf_header_len() {
+        let block = bgzf_block(b"alpha");
+        assert_eq!(bgzf_header_len(&block), Some((18, block.len())));
+        assert_eq!(bgzf_header_len(&gzip(b"alpha")), None);
+        assert_eq!(bgzf_header_len(&block[..11]), None);
+    }
+
+    #[test]
+    fn test_open_gzip_blocks() {
+        let mut compressed = Vec::new();
+        let mut expected: Vec<u8> = Vec::new();
+        // More blocks than a batch holds, several batches being read ahead
+        for i in 0..200 {
+            let content = format!("line {i}\n").repeat(50);
+            compressed.extend(bgzf_block(content.as_bytes()));
+            expected.extend(content.as_bytes());
+        }
+        // The members following the blocks are decompressed as well
+        compressed.extend(gzip(b"the end\n"));
+        expected.extend(b"the end\n");
+
+        assert_eq!(decompress(1, &compressed).unwrap(), expected);
+        assert_eq!(decompress(4, &compressed).unwrap(), expected);
+        assert!(decompress(4, &compressed[..compressed.len() - 30]).is_err());
+
+        // The checksum of every block is checked
+        let mut corrupt = compressed.clone();
+        let first_block_len = bgzf_block("line 0\n".repeat(50).as_bytes()).len();
+        corrupt[first_block_len - 8] ^= 0xff;
+        assert!(decompress(4, &corrupt).is_err());
+    }
+
+    #[test]
+    fn test_open_gzip_read_ahead_error() {
+        let blocks: Vec<_> = (0..40)
+            .map(|i| bgzf_block(format!("{i}\n").as_bytes()))
+            .collect();
+        let mut compressed = blocks.concat();
+        // A stream cut in the middle of the second batch
+        compressed.truncate(compressed.len() - 3);
+        let first_batch: String = (0..2 * BLOCKS_PER_THREAD)
+            .map(|i| format!("{i}\n"))
+            .collect();
+
+        let mut gz = ParallelDecoder::<_, Bgzf>::new(compressed.as_slice(), 2);
+        let mut decompressed = vec![0u8; first_batch.len()];
+        // The content of the first batch is read before the error of the second one
+        gz.read_exact(&mut decompressed).unwrap();
+        assert_eq!(decompressed, first_batch.as_bytes());
+        assert!(gz.read_to_end(&mut decompressed).is_err());
+    }
+
+    #[cfg(feature = "xz")]
+    fn xz_blocks(content: &[u8]) -> Vec<u8> {
+        let stream = liblzma::stream::MtStreamBuilder::new()
+            .threads(2)
+            .block_size(4096)
+            .preset(1)
+            .encoder()
+            .unwrap();
+        let mut encoder = liblzma::write::XzEncoder::new_stream(Vec::new(), stream);
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[cfg(feature = "xz")]
+    fn decompress_xz(threads: usize, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        let size = Some(PARALLEL_XZ_THRESHOLD);
+        let mut xz = open_xz(compressed, size, &Threads(threads))?;
+        let mut decompressed = Vec::new();
+        xz.read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    #[test]
+    #[cfg(feature = "xz")]
+    fn test_open_xz_blocks() {
+        let content: String = (0..2000).map(|i| format!("line {i}\n")).collect();
+        let mut compressed = xz_blocks(content.as_bytes());
+        // The concatenated streams are decompressed as well, after their padding
+        compressed.extend([0; 4]);
+        compressed.extend(xz_blocks(b"the end\n"));
+        let expected = format!("{content}the end\n");
+
+        assert_eq!(decompress_xz(1, &compressed).unwrap(), expected.as_bytes());
+        assert_eq!(decompress_xz(4, &compressed).unwrap(), expected.as_bytes());
+        assert!(decompress_xz(4, &compressed[..compressed.len() - 30]).is_err());
+
+        // The check of every block is verified
+        let mut corrupt = compressed.clone();
+        corrupt[100] ^= 0xff;
+        assert!(decompress_xz(4, &corrupt).is_err());
+    }
+
+    #[cfg(feature = "zstd")]
+    fn zstd_frame(content: &[u8]) -> Vec<u8> {
+        let mut encoder = zstd::stream::write::Encoder::new(Vec::new(), 1).unwrap();
+        encoder.include_checksum(true).unwrap();
+        encoder.write_all(content).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[cfg(feature = "zstd")]
+    fn decompress_zstd(threads: usize, compressed: &[u8]) -> io::Result<Vec<u8>> {
+        let size = Some(PARALLEL_ZSTD_THRESHOLD);
+        let mut zst = open_zstd(compressed, size, &Threads(threads))?;
+        let mut decompressed = Vec::new();
+        zst.read_to_end(&mut decompressed)?;
+        Ok(decompressed)
+    }
+
+    #[test]
+    #[cfg(feature = "zstd")]
+    fn test_open_zstd_frames() {
+        // A skippable frame, as pzstd writes before every frame
+        let mut compressed = vec![0x50, 0x2a, 0x4d, 0x18, 4, 0, 0, 0, 1, 2, 3, 4];
+        let mut expected: Vec<u8> = Vec::new();
+        // More frames than a batch holds, several batches being read ahead
+        for i in 0..20 {
+            let content = format!("line {i}\n").repeat(50);
+            compressed.extend(zstd_frame(content.as_bytes()));
+            expected.extend(content.as_bytes());
+        }
+        // Frames made of RLE blocks
+        compressed.extend(zstd_frame(&[b'z'; 1000]));
+        expected.extend([b'z'; 1000]);
+        // The frames too large to be decompressed in parallel are decompressed as well
+        let large: Vec<u8> = (0..MAX_PARALLEL_FRAME_SIZE + 1000)
+            .map(|i| (i % 7919 % 251) as u8)
+            .collect();
+        compressed.extend(zstd_frame(&large));
+        compressed.extend(zstd_frame(b"the end\n"));
+        expected.extend(&large);
+        expected.extend(b"the end\n");
+
+        assert_eq!(decompress_zstd(1, &compressed).unwrap(), expected);
+        assert_eq!(decompress_zstd(4, &compressed).unwrap(), expected);
+        assert!(decompress_zstd(4, &compressed[..compressed.len() - 30]).is_err());
+
+        // The checksum of every frame is checked
+        let mut corrupt = compressed.clone();
+        let first_frame_len = zstd_frame("line 0\n".repeat(50).as_bytes()).len();
+        corrupt[12 + first_frame_len - 2] ^= 0xff;
+        assert!(decompress_zstd(4, &corrupt).is_err());
+    }
+
+    #[test]
+    fn test_list_entries() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(5);
+        header.set_mode(0o644);
+        header.set_mtime(1_700_000_000);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, "docs/a.txt", b"alpha".as_slice())
+            .unwrap();
+        let tar = builder.into_inner().unwrap();
+
+        let mut entries = Vec::new();
+        let comment = registry()
+            .list_entries(
+                "docs.tar.gz",
+                Cursor::new(gzip(&tar)),
+                "application/gzip",
+                &(),
+                |entry, content| {
+                    let mut text = String::new();
+                    content.read_to_string(&mut text)?;
+                    entries.push((entry.clone(), text));
+                    Ok::<_, Error>(())
+                },
+            )
+            .unwrap();
+        assert_eq!(comment, None);
+        assert_eq!(entries.len(), 1);
+        let (entry, content) = &entries[0];
+        assert_eq!(entry.name, "docs/a.txt");
+        assert_eq!(entry.size, Some(5));
+        assert_eq!(entry.mode.map(|mode| mode & 0o777), Some(0o644));
+        assert_eq!(
+            entry.modified.map(|time| time.timestamp()),
+            Some(1_700_000_000)
+        );
+        assert_eq!(content, "alpha");
+
+        // A compressed file which is not an archive is a single file
+        let mut names = Vec::new();
+        registry()
+            .list_entries(
+                "notes.txt.gz",
+                Cursor::new(gzip(b"notes")),
+                "application/gzip",
+                &(),
+                |entry, _| {
+                    names.push(entry.name.clone());
+                    Ok::<_, Error>(())
+                },
+            )
+            .unwrap();
+        assert_eq!(names, ["notes.txt"]);
+    }
+
+    #[test]
+    fn test_open_entry() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in [("a.txt", "alpha"), ("b.txt", "beta")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+
+        let open = |path| {
+            let mut found = None;
+            let input = Cursor::new(tar.clone());
+            registry()
+                .open_entry("a.tar", input, "application/x-tar", &(), path, |content| {
+                    let mut text = String::new();
+                    content.read_to_string(&mut text)?;
+                    found = Some(text);
+                    Ok::<_, Error>(())
+                })
+                .unwrap();
+            found
+        };
+        assert_eq!(open("b.txt").as_deref(), Some("beta"));
+        assert_eq!(open("c.txt"), None);
+    }
+}
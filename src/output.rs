@@ -0,0 +1,227 @@
+//! The standard output, buffered for the whole run.
+//!
+//! `print!` and `println!` lock and flush the standard output at every line, which makes
+//! dumping large decompressed files slow: everything is printed with `out!` and `outln!`
+//! instead. The buffer is flushed after every file, before prompting for a password and
+//! when zcatr exits, so that the output stays in order with the messages of the standard error.
+//! In a terminal, where this order is visible, it is flushed after every print like `print!` does.
+//!
+//! The inputs processed in parallel print to a buffer of their own instead (see `capture`),
+//! printed once their turn comes.
+//!
+//! Once the standard output is closed by the command reading it (e.g. `head` or a pager quit
+//! before the end), zcatr exits quietly at the next write, see `QuietStdout`.
+
+use std::{
+    cell::RefCell,
+    fmt,
+    io::{self, BufWriter, IsTerminal, Stdout, Write},
+    sync::{LazyLock, Mutex, MutexGuard, PoisonError},
+};
+
+/// Size of the buffer of the standard output.
+const OUTPUT_BUFFER_SIZE: usize = 64 * 1024;
+
+static STDOUT: LazyLock<Mutex<BufWriter<QuietStdout>>> = LazyLock::new(|| {
+    Mutex::new(BufWriter::with_capacity(
+        OUTPUT_BUFFER_SIZE,
+        QuietStdout(io::stdout()),
+    ))
+});
+
+/// Whether the standard output is a terminal, once the pager (if any) is started.
+static IS_TERMINAL: LazyLock<bool> = LazyLock::new(|| io::stdout().is_terminal());
+
+/// The standard output, exiting zcatr successfully once it is closed by the command reading
+/// it, whose output needs nothing more.
+///
+/// SIGPIPE being ignored, like in every Rust program, writing to a closed pipe fails with
+/// `io::ErrorKind::BrokenPipe` instead of killing zcatr.
+pub(crate) struct QuietStdout(Stdout);
+
+impl QuietStdout {
+    /// Exits if the standard output was closed, returning the other results as they are.
+    fn check<T>(result: io::Result<T>) -> io::Result<T> {
+        match result {
+            // `exit` would flush the buffered output again, whose lock is held
+            Err(err) if err.kind() == io::ErrorKind::BrokenPipe => std::process::exit(0),
+            result => result,
+        }
+    }
+}
+
+impl Write for QuietStdout {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        Self::check(self.0.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Self::check(self.0.flush())
+    }
+}
+
+thread_local! {
+    /// What the thread printed while its output is captured, see `capture`.
+    static CAPTURED: RefCell<Option<Captured>> = const { RefCell::new(None) };
+}
+
+/// What a function printed, see `capture`.
+#[derive(Debug, Default)]
+pub(crate) struct Captured {
+    output: Vec<u8>,
+    /// The messages of `errln!`, each followed by a line ending
+    errors: String,
+}
+
+impl Captured {
+    /// Prints what was captured: the output, then the messages of the standard error.
+    pub(crate) fn print(self) {
+        let mut stdout = stdout();
+        if let Err(err) = stdout.write_all(&self.output).and_then(|()| stdout.flush()) {
+            panic!("failed printing to stdout: {err}");
+        }
+        eprint!("{}", self.errors);
+    }
+
+    /// Returns the output captured, for the tests.
+    #[cfg(test)]
+    pub(crate) fn output(&self) -> &[u8] {
+        &self.output
+    }
+}
+
+/// Runs a function, keeping what it prints with `out!`, `outln!`, `errln!` and `StdoutWriter`
+/// aside instead of printing it, e.g. to print the output of inputs processed in parallel in
+/// the order they were given.
+pub(crate) fn capture<T>(function: impl FnOnce() -> T) -> (T, Captured) {
+    CAPTURED.set(Some(Captured::default()));
+    let result = function();
+    (result, CAPTURED.take().unwrap_or_default())
+}
+
+/// Tells whether the output of the thread is captured, see `capture`.
+pub(crate) fn is_captured() -> bool {
+    CAPTURED.with_borrow(Option::is_some)
+}
+
+/// Runs a function on what the thread captured, if its output is captured (see `capture`).
+fn with_captured<T>(function: impl FnOnce(&mut Captured) -> T) -> Option<T> {
+    CAPTURED.with_borrow_mut(|captured| captured.as_mut().map(function))
+}
+
+/// Prints to the buffered standard output, like `print!`.
+macro_rules! out {
+    ($($arg:tt)*) => {
+        $crate::output::print(format_args!($($arg)*), false)
+    };
+}
+
+/// Prints to the buffered standard output followed by a line ending, like `println!`.
+macro_rules! outln {
+    () => {
+        $crate::output::print(format_args!(""), true)
+    };
+    ($($arg:tt)*) => {
+        $crate::output::print(format_args!($($arg)*), true)
+    };
+}
+
+/// Prints to the standard error followed by a line ending, like `eprintln!`, unless the
+/// output is captured (see `capture`).
+macro_rules! errln {
+    ($($arg:tt)*) => {
+        $crate::output::print_error(format_args!($($arg)*))
+    };
+}
+
+/// Locks the buffered standard output, for the code writing to it directly.
+pub(crate) fn stdout() -> MutexGuard<'static, BufWriter<QuietStdout>> {
+    STDOUT.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// The buffered standard output, for the code writing to any `Write` (e.g.
+/// `display_file_content`), the tests writing to a `Vec<u8>` instead.
+///
+/// Like `out!`, it is locked at every write and flushed after it in a terminal.
+pub(crate) struct StdoutWriter;
+
+impl Write for StdoutWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.write_all(buf).map(|()| buf.len())
+    }
+
+    fn write_all(&mut self, buf: &[u8]) -> io::Result<()> {
+        if with_captured(|captured| captured.output.extend_from_slice(buf)).is_some() {
+            return Ok(());
+        }
+        let mut stdout = stdout();
+        stdout.write_all(buf)?;
+        match *IS_TERMINAL {
+            true => stdout.flush(),
+            false => Ok(()),
+        }
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        match is_captured() {
+            true => Ok(()),
+            false => stdout().flush(),
+        }
+    }
+}
+
+/// Prints formatted text to the buffered standard output, see `out!` and `outln!`.
+///
+/// # Panics
+/// If the standard output cannot be written to, like `print!`, unless it was closed (see
+/// `QuietStdout`)
+pub(crate) fn print(args: fmt::Arguments<'_>, new_line: bool) {
+    let captured = with_captured(|captured| {
+        // Writing to a vector never fails
+        let _ = captured.output.write_fmt(args);
+        if new_line {
+            captured.output.push(b'\n');
+        }
+    });
+    if captured.is_some() {
+        return;
+    }
+    let mut stdout = stdout();
+    let result = match new_line {
+        true => stdout
+            .write_fmt(args)
+            .and_then(|()| stdout.write_all(b"\n")),
+        false => stdout.write_fmt(args),
+    }
+    .and_then(|()| match *IS_TERMINAL {
+        true => stdout.flush(),
+        false => Ok(()),
+    });
+    if let Err(err) = result {
+        panic!("failed printing to stdout: {err}");
+    }
+}
+
+/// Prints a message to the standard error, see `errln!`.
+pub(crate) fn print_error(args: fmt::Arguments<'_>) {
+    let captured = with_captured(|captured| {
+        captured.errors.push_str(&args.to_string());
+        captured.errors.push('\n');
+    });
+    if captured.is_none() {
+        eprintln!("{args}");
+    }
+}
+
+/// Writes the buffered output to the standard output.
+///
+/// Errors are ignored, as they are reported by the next print if the output is still used.
+pub(crate) fn flush() {
+    let _ = stdout().flush();
+}
+
+/// Flushes the buffered output and exits with the given code, see `std::process::exit`.
+pub(crate) fn exit(code: i32) -> ! {
+    flush();
+    std::process::exit(code)
+}
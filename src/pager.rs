@@ -0,0 +1,86 @@
+//! Paging of the output when it is displayed in a terminal, like git does.
+//!
+//! The standard output of the process is redirected to the pager once it is started, so that
+//! everything printed afterwards goes through it.
+
+/// The pager used when `$PAGER` is not set: it keeps the colors, exits right away when the
+/// output fits on the screen and leaves the output on the screen once it is quit.
+#[cfg(unix)]
+const DEFAULT_PAGER: &str = "less -RFX";
+
+/// Process ID of the running pager, waited for when zcatr exits.
+#[cfg(unix)]
+static PAGER_PID: std::sync::atomic::AtomicI32 = std::sync::atomic::AtomicI32::new(0);
+
+/// Sends the rest of the output through a pager, if the standard output is a terminal.
+///
+/// The pager is `$PAGER`, run by the shell, else the one of the configuration file, or
+/// `less -RFX` by default. An empty `$PAGER` or `cat` disables the paging, and so does any
+/// error starting the pager.
+///
+/// # Arguments
+/// * `configured` - The pager set in the configuration file, if any
+#[cfg(unix)]
+pub(crate) fn start(configured: Option<&str>) {
+    use std::{
+        io::{self, IsTerminal},
+        os::fd::AsRawFd,
+        process::{Command, Stdio},
+        sync::atomic::Ordering,
+    };
+
+    if !io::stdout().is_terminal() {
+        return;
+    }
+    let pager =
+        std::env::var("PAGER").unwrap_or_else(|_| configured.unwrap_or(DEFAULT_PAGER).to_owned());
+    if matches!(pager.trim(), "" | "cat") {
+        return;
+    }
+
+    let mut command = Command::new("sh");
+    command.arg("-c").arg(&pager).stdin(Stdio::piped());
+    // Same as git, for a `$PAGER` of just `less`
+    if std::env::var_os("LESS").is_none() {
+        command.env("LESS", "FRX");
+    }
+    let Ok(mut child) = command.spawn() else {
+        return;
+    };
+    let Some(pager_input) = child.stdin.take() else {
+        return;
+    };
+
+    // Quitting the pager before the end of the output stops zcatr quietly, see
+    // `output::QuietStdout`
+    // SAFETY: only file descriptors of the process are changed, and `wait` only calls
+    // async-signal-safe functions
+    unsafe {
+        if libc::dup2(pager_input.as_raw_fd(), libc::STDOUT_FILENO) < 0 {
+            let _ = child.kill();
+            return;
+        }
+        PAGER_PID.store(child.id() as i32, Ordering::Relaxed);
+        libc::atexit(wait);
+    }
+}
+
+/// Paging is only supported on Unix, the output is left as is elsewhere.
+#[cfg(not(unix))]
+pub(crate) fn start(_configured: Option<&str>) {}
+
+/// Closes the output and waits for the pager to be quit, so that the shell only gets the
+/// terminal back afterwards. Registered to run when zcatr exits, after its output is flushed.
+#[cfg(unix)]
+extern "C" fn wait() {
+    let pid = PAGER_PID.load(std::sync::atomic::Ordering::Relaxed);
+    // SAFETY: the pager is a child of the process, and nothing is written to the standard
+    // output once the process exits
+    unsafe {
+        libc::close(libc::STDOUT_FILENO);
+        let mut status = 0;
+        while libc::waitpid(pid, &mut status, 0) < 0
+            && std::io::Error::last_os_error().kind() == std::io::ErrorKind::Interrupted
+        {}
+    }
+}
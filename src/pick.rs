@@ -0,0 +1,434 @@
+//! Interactive fuzzy finder picking a file of every input, see `--pick`.
+//!
+//! The names of the files are matched against the query like skim and fzf do: the
+//! characters of the query must appear in the name in the same order. The finder is drawn on
+//! the standard error, so that the standard output only receives the picked file.
+
+use std::{
+    io::{self, IsTerminal, Seek},
+    path::{Path, PathBuf},
+};
+
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind, KeyModifiers},
+        execute,
+        terminal::{self, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout, Position},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{List, ListItem, ListState},
+    Frame, Terminal,
+};
+
+use crate::{
+    display_file_content, handle_entries, output, write_decompressed, Context, ExitStatus, Input,
+    OutputTarget, ZcatError,
+};
+
+/// A file picked in an input, see `--pick`.
+pub(crate) struct Picked {
+    file_path: PathBuf,
+    /// The input, rewound to its start
+    input: Input,
+    file_type: &'static str,
+    /// The name of the file, or the path of the input when it is not an archive
+    name: String,
+    /// The position of the file in the input, starting at 1
+    index: usize,
+}
+
+impl Picked {
+    pub(crate) fn file_path(&self) -> &Path {
+        &self.file_path
+    }
+
+    /// Displays the picked file, or writes it to the target of `--output` or `--output-dir`.
+    ///
+    /// # Arguments
+    /// * `context` - The options of the command line
+    /// * `target` - The target of `--output` or `--output-dir`, if any
+    ///
+    /// # Returns
+    /// * `Ok(())` if all operations succeeded
+    /// * `Err(ZcatError)` if the input could not be read or the content could not be written
+    pub(crate) fn output(
+        self,
+        context: &Context,
+        target: Option<&mut OutputTarget>,
+    ) -> Result<(), ZcatError> {
+        if let Some(target) = target {
+            return write_decompressed(
+                context,
+                &self.file_path,
+                self.input,
+                self.file_type,
+                target,
+                Some(self.index),
+            );
+        }
+        let mut index = 0;
+        handle_entries(
+            context,
+            &self.file_path,
+            self.input,
+            self.file_type,
+            |_, content| {
+                index += 1;
+                if index == self.index {
+                    display_file_content(
+                        context,
+                        &mut output::StdoutWriter,
+                        &self.name,
+                        None,
+                        content,
+                    )?;
+                }
+                Ok(())
+            },
+        )
+    }
+}
+
+/// Lets the user pick a file of every input in turn, the inputs holding a single file being
+/// picked without asking.
+///
+/// The inputs which cannot be read twice (e.g. the standard input) are loaded in memory, so
+/// that the picked file can be read afterwards.
+///
+/// # Arguments
+/// * `context` - The options of the command line
+/// * `files` - The inputs
+///
+/// # Returns
+/// The files picked, along with the exit status of the inputs which could not be read, or
+/// `None` if the picking was cancelled
+pub(crate) fn pick_entries(
+    context: &Context,
+    files: &[PathBuf],
+) -> Option<(Vec<Picked>, ExitStatus)> {
+    if !io::stderr().is_terminal() {
+        eprintln!("--pick needs a terminal to display the files to pick from");
+        ExitStatus::Usage.exit();
+    }
+
+    let mut picks = Vec::new();
+    let mut status = ExitStatus::Success;
+    for file_path in files {
+        let names =
+            Input::open_rewindable(context, file_path).and_then(|(mut input, file_type)| {
+                let mut names = Vec::new();
+                handle_entries(context, file_path, &mut input, file_type, |name, _| {
+                    names.push(name.map_or_else(|| file_path.display().to_string(), str::to_owned));
+                    Ok(())
+                })?;
+                input.rewind()?;
+                Ok((input, file_type, names))
+            });
+        let (input, file_type, names) = match names {
+            Ok(opened) => opened,
+            Err(err) => {
+                eprintln!(
+                    "An error occurred while processing the file: {:?}. Error: {}",
+                    file_path, err
+                );
+                status = status.max(err.exit_status());
+                continue;
+            }
+        };
+
+        let index = match names.len() {
+            0 => {
+                eprintln!("There is no file to pick in the following file: {file_path:?}");
+                status = status.max(ExitStatus::Failure);
+                continue;
+            }
+            1 => 0,
+            _ => match pick(&file_path.display().to_string(), &names) {
+                Ok(Some(index)) => index,
+                Ok(None) => return None,
+                Err(err) => {
+                    eprintln!("The terminal could not be used. Error: {err}");
+                    ExitStatus::Failure.exit();
+                }
+            },
+        };
+        picks.push(Picked {
+            file_path: file_path.clone(),
+            input,
+            file_type,
+            name: names[index].clone(),
+            index: index + 1,
+        });
+    }
+    Some((picks, status))
+}
+
+/// Scores how well a name matches the query, ignoring the case: the characters of the query
+/// must appear in the name in the same order. Consecutive characters and characters starting
+/// a path component or a word score higher.
+///
+/// # Arguments
+/// * `query` - The text typed
+/// * `name` - The name of a file
+///
+/// # Returns
+/// The score, higher for better matches, and the positions of the characters matched in the
+/// name, or `None` if the name does not match
+pub(crate) fn fuzzy_match(query: &str, name: &str) -> Option<(i64, Vec<usize>)> {
+    let mut query = query.chars().flat_map(char::to_lowercase).peekable();
+    let mut score = 0;
+    let mut positions = Vec::new();
+    let mut previous = None;
+    for (position, char) in name.chars().enumerate() {
+        let Some(&expected) = query.peek() else {
+            break;
+        };
+        if char.to_lowercase().eq([expected]) {
+            score += 1;
+            if positions.last() == Some(&position.wrapping_sub(1)) {
+                score += 4;
+            }
+            if previous.is_none_or(|previous: char| !previous.is_alphanumeric()) {
+                score += 3;
+            }
+            positions.push(position);
+            query.next();
+        }
+        previous = Some(char);
+    }
+    query.peek().is_none().then_some((score, positions))
+}
+
+/// The state of the finder.
+struct Finder<'a> {
+    names: &'a [String],
+    query: String,
+    /// The indexes of the names matching the query, best matches first, with the positions
+    /// of the characters matched
+    matches: Vec<(usize, Vec<usize>)>,
+    state: ListState,
+}
+
+impl Finder<'_> {
+    fn refresh(&mut self) {
+        let mut matches: Vec<(i64, usize, Vec<usize>)> = self
+            .names
+            .iter()
+            .enumerate()
+            .filter_map(|(index, name)| {
+                fuzzy_match(&self.query, name).map(|(score, positions)| (score, index, positions))
+            })
+            .collect();
+        // Shorter names first among the matches scoring the same, the archive order without query
+        if !self.query.is_empty() {
+            matches.sort_by_key(|(score, index, _)| (-score, self.names[*index].len(), *index));
+        }
+        self.matches = matches
+            .into_iter()
+            .map(|(_, index, positions)| (index, positions))
+            .collect();
+        self.state.select_first();
+    }
+
+    fn run(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<io::Stderr>>,
+        prompt: &str,
+    ) -> io::Result<Option<usize>> {
+        loop {
+            terminal.draw(|frame| self.draw(frame, prompt))?;
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let control = key.modifiers.contains(KeyModifiers::CONTROL);
+            match key.code {
+                KeyCode::Esc => return Ok(None),
+                KeyCode::Char('c' | 'g') if control => return Ok(None),
+                KeyCode::Enter => {
+                    let selected = self
+                        .state
+                        .selected()
+                        .and_then(|index| self.matches.get(index));
+                    if let Some((index, _)) = selected {
+                        return Ok(Some(*index));
+                    }
+                }
+                KeyCode::Up => self.state.select_previous(),
+                KeyCode::Char('p' | 'k') if control => self.state.select_previous(),
+                KeyCode::Down => self.state.select_next(),
+                KeyCode::Char('n' | 'j') if control => self.state.select_next(),
+                KeyCode::Char('u') if control => {
+                    self.query.clear();
+                    self.refresh();
+                }
+                KeyCode::Backspace => {
+                    self.query.pop();
+                    self.refresh();
+                }
+                KeyCode::Char(char) if !control => {
+                    self.query.push(char);
+                    self.refresh();
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn draw(&mut self, frame: &mut Frame, prompt: &str) {
+        let [query, info, list] = Layout::vertical([
+            Constraint::Length(1),
+            Constraint::Length(1),
+            Constraint::Fill(1),
+        ])
+        .areas(frame.area());
+
+        let query_line = format!("{prompt}> {}", self.query);
+        frame.set_cursor_position(Position::new(
+            query.x + query_line.chars().count() as u16,
+            query.y,
+        ));
+        frame.render_widget(Line::from(query_line), query);
+        frame.render_widget(
+            Line::from(format!("  {}/{}", self.matches.len(), self.names.len())),
+            info,
+        );
+
+        let matched = Style::new().add_modifier(Modifier::BOLD | Modifier::UNDERLINED);
+        let items: Vec<ListItem> = self
+            .matches
+            .iter()
+            .map(|(index, positions)| {
+                let spans: Vec<Span> = self.names[*index]
+                    .chars()
+                    .enumerate()
+                    .map(|(position, char)| match positions.contains(&position) {
+                        true => Span::styled(char.to_string(), matched),
+                        false => Span::raw(char.to_string()),
+                    })
+                    .collect();
+                ListItem::new(Line::from(spans))
+            })
+            .collect();
+        let list_widget = List::new(items)
+            .highlight_symbol("> ")
+            .highlight_style(Style::new().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list_widget, list, &mut self.state);
+    }
+}
+
+/// Lets the user pick a name with the finder, drawn on the standard error.
+///
+/// # Arguments
+/// * `prompt` - The text displayed before the query, the name of the input
+/// * `names` - The names to pick from
+///
+/// # Returns
+/// The index of the name picked, or `None` if the picking was cancelled
+fn pick(prompt: &str, names: &[String]) -> io::Result<Option<usize>> {
+    let mut finder = Finder {
+        names,
+        query: String::new(),
+        matches: Vec::new(),
+        state: ListState::default(),
+    };
+    finder.refresh();
+
+    terminal::enable_raw_mode()?;
+    let result = execute!(io::stderr(), EnterAlternateScreen)
+        .and_then(|()| Terminal::new(CrosstermBackend::new(io::stderr())))
+        .and_then(|mut terminal| finder.run(&mut terminal, prompt));
+    let _ = execute!(io::stderr(), LeaveAlternateScreen);
+    let _ = terminal::disable_raw_mode();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn finder<'a>(names: &'a [String], query: &str) -> Vec<&'a str> {
+        let mut finder = Finder {
+            names,
+            query: query.to_owned(),
+            matches: Vec::new(),
+            state: ListState::default(),
+        };
+        finder.refresh();
+        assert_eq!(finder.state.selected(), Some(0));
+        finder
+            .matches
+            .iter()
+            .map(|(index, _)| names[*index].as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_finder_refresh() {
+        let names = [
+            "docs/changelog.md",
+            "src/main.rs",
+            "src/domain.rs",
+            "main.rs",
+            "README.md",
+        ]
+        .map(str::to_owned);
+
+        // The best matches first, the shorter names first among those scoring the same
+        assert_eq!(
+            finder(&names, "main"),
+            ["main.rs", "src/main.rs", "src/domain.rs"]
+        );
+        let names_scoring_the_same = ["logs/b.txt", "a.txt"].map(str::to_owned);
+        assert_eq!(
+            finder(&names_scoring_the_same, "TXT"),
+            ["a.txt", "logs/b.txt"]
+        );
+        assert!(finder(&names, "xyz").is_empty());
+    }
+
+    #[test]
+    fn test_finder_refresh_without_query() {
+        let names = ["b.txt", "a/long/path.txt", "c.txt"].map(str::to_owned);
+        // All the names, in the order of the archive
+        assert_eq!(finder(&names, ""), names);
+    }
+
+    #[test]
+    fn test_picked_output() {
+        let mut builder = tar::Builder::new(Vec::new());
+        for (name, content) in [("a.txt", "alpha"), ("b.txt", "beta"), ("c.txt", "gamma")] {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, name, content.as_bytes())
+                .unwrap();
+        }
+        let tar = builder.into_inner().unwrap();
+        let picked = |index: usize| Picked {
+            file_path: PathBuf::from("test.tar"),
+            input: Input::Memory(io::Cursor::new(tar.clone())),
+            file_type: "application/x-tar",
+            name: ["a.txt", "b.txt", "c.txt"][index - 1].to_owned(),
+            index,
+        };
+        let context = Context::default();
+
+        let (result, captured) = output::capture(|| picked(2).output(&context, None));
+        result.unwrap();
+        assert_eq!(captured.output(), b"beta");
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("picked.txt");
+        let mut target = OutputTarget::File(std::fs::File::create(&path).unwrap());
+        picked(3).output(&context, Some(&mut target)).unwrap();
+        drop(target);
+        assert_eq!(std::fs::read(&path).unwrap(), b"gamma");
+    }
+}
@@ -0,0 +1,154 @@
+//! The in-house formats of the configuration file, read by external commands, see `[[format]]`.
+//!
+//! They are registered in the registry of the formats zcatr reads (see `zcatr::Registry`), and
+//! are therefore read wherever the built-in ones are: on the command line, in archives with
+//! `--recurse-archives`, by `zcatr grep`, ... The content of an input is loaded in memory
+//! (within `--max-memory`) and written to the standard input of the command, whose standard
+//! output is read like the decompressed content of a GZIP file: the files of the TAR archive it
+//! holds, or a single file named after the input.
+
+use std::{
+    io::{self, Read, Write},
+    process::{Command, Stdio},
+    thread,
+};
+
+use zcatr::{
+    decompressed_name, for_each_decompressed_entry, list_decompressed_entries, Entry, EntryHandler,
+    ListingHandler, Options, ReadSeek,
+};
+
+use crate::{config::FormatPlugin, read_to_end_limited};
+
+impl zcatr::Format for FormatPlugin {
+    fn mime_type(&self) -> &'static str {
+        self.mime_type
+    }
+
+    fn sniff(&self, magic_bytes: &[u8]) -> bool {
+        self.magic.as_ref().is_some_and(|magic| {
+            magic_bytes
+                .get(self.offset..)
+                .is_some_and(|bytes| bytes.starts_with(magic))
+        })
+    }
+
+    fn matches_name(&self, name: &str) -> bool {
+        let name = name.to_lowercase();
+        self.extensions.iter().any(|extension| {
+            name.strip_suffix(extension.as_str())
+                .is_some_and(|stem| stem.ends_with('.'))
+        })
+    }
+
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn decoder(&self) -> &str {
+        "command of the configuration file"
+    }
+
+    fn for_each_entry(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut EntryHandler,
+    ) -> Result<(), zcatr::Error> {
+        self.decode(input, options, &mut |output| {
+            for_each_decompressed_entry(decompressed_name(name), output, options, handler)
+        })
+    }
+
+    fn list_entries(
+        &self,
+        name: &str,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handler: &mut ListingHandler,
+    ) -> Result<Option<String>, zcatr::Error> {
+        self.decode(input, options, &mut |output| {
+            let file = Entry {
+                name: decompressed_name(name),
+                ..Default::default()
+            };
+            list_decompressed_entries(file, output, options, handler)
+        })?;
+        Ok(None)
+    }
+}
+
+impl FormatPlugin {
+    /// Runs the command of the format on an input, handing its output over to a function.
+    ///
+    /// # Arguments
+    /// * `input` - The raw content of the input, from its start
+    /// * `options` - The settings of the reading
+    /// * `handle` - A function reading the output of the command, its decoded content
+    ///
+    /// # Errors
+    /// * `zcatr::Error::Io` - If the command could not be run or failed
+    /// * Any error of `handle`, the command being killed
+    fn decode(
+        &self,
+        input: &mut dyn ReadSeek,
+        options: &dyn Options,
+        handle: &mut dyn FnMut(&mut dyn Read) -> Result<(), zcatr::Error>,
+    ) -> Result<(), zcatr::Error> {
+        // The command is fed from another thread, which the input cannot be handed over to
+        let mut content = Vec::new();
+        read_to_end_limited(options, input, &mut content)?;
+        let (program, args) = self
+            .command
+            .split_first()
+            .expect("the command is checked when the configuration is loaded");
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()
+            .map_err(|err| io::Error::other(format!("could not run {program}: {err}")))?;
+        let (Some(mut stdin), Some(stdout)) = (child.stdin.take(), child.stdout.take()) else {
+            unreachable!("the standard input and output of the command are piped");
+        };
+
+        let result = thread::scope(|scope| {
+            let content = &content;
+            // Written from another thread, as the command may only read its input as its
+            // output is read. Closing the input afterwards tells the command it has it all.
+            scope.spawn(move || stdin.write_all(content));
+            let mut output = options.wrap(Box::new(stdout), Some(content.len() as u64));
+            let result = handle(&mut output)
+                // The command could not write the rest of its output, and would not exit
+                .and_then(|()| Ok(io::copy(&mut output, &mut io::sink()).map(drop)?));
+            if result.is_err() {
+                let _ = child.kill();
+            }
+            result
+        });
+        let status = child.wait()?;
+        result?;
+        if !status.success() {
+            return Err(io::Error::other(format!(
+                "{program} could not decode the file ({status})"
+            ))
+            .into());
+        }
+        Ok(())
+    }
+}
+
+/// Returns the registry of the formats zcatr reads: the built-in ones and those of the
+/// configuration file, which take precedence.
+///
+/// # Arguments
+/// * `plugins` - The `[[format]]` of the configuration file
+pub(crate) fn registry(plugins: &[FormatPlugin]) -> zcatr::Registry {
+    let mut registry = zcatr::Registry::default();
+    for plugin in plugins {
+        registry.register(plugin.clone());
+    }
+    registry
+}
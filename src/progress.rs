@@ -0,0 +1,161 @@
+//! Progress bars of the operations reading whole inputs (listings, tests, extraction).
+//!
+//! The bar of an input is drawn on the standard error once the input has been processed for
+//! `PROGRESS_DELAY`, so that small inputs do not make it flicker, and only in a terminal.
+
+use std::{
+    sync::{atomic::Ordering, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+
+use indicatif::{ProgressBar, ProgressDrawTarget, ProgressStyle};
+
+use crate::{format_size_in, output, Context, SizeUnits, DECOMPRESSED_BYTES};
+
+/// How long an input is processed before its progress bar is drawn.
+const PROGRESS_DELAY: Duration = Duration::from_secs(1);
+
+/// How often the message of a progress bar is updated.
+const REFRESH_INTERVAL: Duration = Duration::from_millis(100);
+
+/// The progress of the input being processed, see `start`.
+static PROGRESS: Mutex<Option<Progress>> = Mutex::new(None);
+
+struct Progress {
+    bar: ProgressBar,
+    started: Instant,
+    refreshed: Instant,
+    visible: bool,
+    entries: u64,
+    /// The value of `DECOMPRESSED_BYTES` when the input was opened
+    decompressed_before: u64,
+    /// See `--si`, `--binary` and `--bytes`
+    size_units: SizeUnits,
+}
+
+impl Progress {
+    /// Updates the message of the bar, and draws it once the input is processed for long enough.
+    fn refresh(&mut self) {
+        let now = Instant::now();
+        if now.duration_since(self.refreshed) < REFRESH_INTERVAL {
+            return;
+        }
+        self.refreshed = now;
+
+        let mut message = format_size_in(self.bar.position(), self.size_units);
+        if let Some(len) = self.bar.length() {
+            message.push_str(&format!(" of {}", format_size_in(len, self.size_units)));
+        }
+        if self.entries > 0 {
+            message.push_str(&format!(", {} entries", self.entries));
+        }
+        let decompressed = DECOMPRESSED_BYTES.load(Ordering::Relaxed) - self.decompressed_before;
+        message.push_str(&format!(
+            ", {} decompressed",
+            format_size_in(decompressed, self.size_units)
+        ));
+        self.bar.set_message(message);
+        if !self.visible && now.duration_since(self.started) >= PROGRESS_DELAY {
+            self.visible = true;
+            self.bar.set_draw_target(ProgressDrawTarget::stderr());
+        }
+    }
+}
+
+/// Clears the progress bar of an input when dropped, see `start`.
+pub(crate) struct ProgressGuard(());
+
+impl Drop for ProgressGuard {
+    fn drop(&mut self) {
+        let progress = PROGRESS
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .take();
+        if let Some(progress) = progress {
+            progress.bar.finish_and_clear();
+        }
+    }
+}
+
+/// Starts reporting the progress of an input, until the returned guard is dropped.
+///
+/// Nothing is reported unless enabled in the context (see `--no-progress`), nor for the inputs
+/// processed in parallel, whose output is captured (see `output::capture`).
+///
+/// # Arguments
+/// * `context` - The options of the command line
+/// * `name` - The name of the input, displayed before the bar
+/// * `len` - The size of the input, if known; a spinner is displayed otherwise
+pub(crate) fn start(context: &Context, name: &str, len: Option<u64>) -> ProgressGuard {
+    if !context.progress || output::is_captured() {
+        return ProgressGuard(());
+    }
+
+    let bar = ProgressBar::with_draw_target(len, ProgressDrawTarget::hidden());
+    let template = match len {
+        Some(_) => "{prefix} [{bar:30}] {msg} ({eta})",
+        None => "{spinner} {prefix} {msg}",
+    };
+    let mut style = ProgressStyle::with_template(template)
+        .unwrap()
+        .progress_chars("=> ");
+    if context.ascii {
+        // The default spinner is made of braille patterns
+        style = style.tick_chars("|/-\\ ");
+    }
+    bar.set_style(style);
+    bar.set_prefix(name.to_owned());
+
+    let now = Instant::now();
+    *PROGRESS.lock().unwrap_or_else(PoisonError::into_inner) = Some(Progress {
+        bar,
+        started: now,
+        refreshed: now,
+        visible: false,
+        entries: 0,
+        decompressed_before: DECOMPRESSED_BYTES.load(Ordering::Relaxed),
+        size_units: context.size_units,
+    });
+    ProgressGuard(())
+}
+
+/// Reports that bytes were read from the input.
+pub(crate) fn advance(bytes: usize) {
+    if let Some(progress) = PROGRESS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        progress.bar.inc(bytes as u64);
+        progress.refresh();
+    }
+}
+
+/// Reports that an entry of the input is being processed.
+pub(crate) fn entry() {
+    if let Some(progress) = PROGRESS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_mut()
+    {
+        progress.entries += 1;
+        progress.refresh();
+    }
+}
+
+/// Runs a function writing to the terminal with the progress bar hidden, if it is drawn.
+pub(crate) fn suspend<F, R>(f: F) -> R
+where
+    F: FnOnce() -> R,
+{
+    let bar = PROGRESS
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .as_ref()
+        .filter(|progress| progress.visible)
+        .map(|progress| progress.bar.clone());
+    match bar {
+        Some(bar) => bar.suspend(f),
+        None => f(),
+    }
+}
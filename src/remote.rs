@@ -0,0 +1,616 @@
+//! Inputs downloaded from a server, given as URLs instead of paths.
+//!
+//! S3 objects (`s3://bucket/key`) are downloaded through a presigned HTTPS URL when zcatr is
+//! built with the `s3` feature, so they are read like any other URL. Files on SSH servers
+//! (`user@host:path`) are read over SFTP when it is built with the `sftp` feature.
+
+use std::{
+    fmt,
+    io::{self, Read, Seek},
+    path::Path,
+    time::Duration,
+};
+
+use crate::ZcatError;
+
+/// The schemes of the URLs accepted as inputs.
+const URL_SCHEMES: [&str; 3] = ["http://", "https://", S3_SCHEME];
+
+const S3_SCHEME: &str = "s3://";
+
+/// How long the URLs presigned to download S3 objects are valid.
+#[cfg(feature = "s3")]
+const PRESIGNED_URL_LIFETIME: std::time::Duration = std::time::Duration::from_secs(3600);
+
+/// Size of the first range requested by a `RangeReader`, and after each seek.
+const MIN_RANGE_SIZE: u64 = 64 * 1024;
+
+/// Size the ranges requested by a `RangeReader` grow to while it is read sequentially.
+const MAX_RANGE_SIZE: u64 = 8 * 1024 * 1024;
+
+/// The port SSH servers are reached on.
+#[cfg(feature = "sftp")]
+const SSH_PORT: u16 = 22;
+
+/// Tells whether an input is a URL rather than the path of a local file.
+pub(crate) fn is_url(path: &Path) -> bool {
+    path.to_str().is_some_and(|path| {
+        URL_SCHEMES.iter().any(|scheme| {
+            path.get(..scheme.len())
+                .is_some_and(|prefix| prefix.eq_ignore_ascii_case(scheme))
+        })
+    })
+}
+
+/// Tells whether an input is a remote file, given as a URL or as `user@host:path`, rather
+/// than the path of a local file.
+pub(crate) fn is_remote(path: &Path) -> bool {
+    is_url(path) || SftpLocation::of(path).is_some()
+}
+
+/// Returns the name of the file a remote input points to: the last segment of its path,
+/// without the query string and fragment of URLs.
+///
+/// # Examples
+/// `https://example.com/releases/app.tar.gz?token=abc` gives `app.tar.gz`
+pub(crate) fn file_name(url: &str) -> &str {
+    if let Some(location) = SftpLocation::parse(url) {
+        return location.path.rsplit('/').next().unwrap_or(location.path);
+    }
+    let url = url.split(['?', '#']).next().unwrap_or(url);
+    let path = url.split_once("://").map_or(url, |(_, rest)| rest);
+    match path.split_once('/') {
+        Some((_, path)) => path.rsplit('/').next().unwrap_or(path),
+        None => "",
+    }
+}
+
+fn is_s3_url(url: &str) -> bool {
+    url.get(..S3_SCHEME.len())
+        .is_some_and(|prefix| prefix.eq_ignore_ascii_case(S3_SCHEME))
+}
+
+/// Splits an S3 URL into its bucket and key.
+///
+/// # Examples
+/// `s3://logs/2024/app.log.gz` gives `("logs", "2024/app.log.gz")`
+fn parse_s3_url(url: &str) -> Result<(&str, &str), ZcatError> {
+    url[S3_SCHEME.len()..]
+        .split_once('/')
+        .filter(|(bucket, key)| !bucket.is_empty() && !key.is_empty())
+        .ok_or_else(|| ZcatError::S3Error(format!("{url:?} is not of the form s3://bucket/key")))
+}
+
+/// Signs a GET request for an S3 object, with the credentials and region found by the
+/// AWS SDK (environment, shared config and credentials files, SSO, instance metadata…).
+///
+/// # Returns
+/// * `Ok(String)` - The presigned HTTPS URL of the object
+/// * `Err(ZcatError::S3Error)` - If the URL is invalid or no credentials could be found
+#[cfg(feature = "s3")]
+fn presign_s3_url(url: &str) -> Result<String, ZcatError> {
+    use aws_sdk_s3::{error::DisplayErrorContext, presigning::PresigningConfig};
+
+    let (bucket, key) = parse_s3_url(url)?;
+    let s3_error =
+        |err: &dyn std::error::Error| ZcatError::S3Error(DisplayErrorContext(err).to_string());
+    let runtime = tokio::runtime::Builder::new_current_thread()
+        .enable_all()
+        .build()?;
+    runtime.block_on(async {
+        let config = aws_config::load_from_env().await;
+        let presigning =
+            PresigningConfig::expires_in(PRESIGNED_URL_LIFETIME).map_err(|err| s3_error(&err))?;
+        let request = aws_sdk_s3::Client::new(&config)
+            .get_object()
+            .bucket(bucket)
+            .key(key)
+            .presigned(presigning)
+            .await
+            .map_err(|err| s3_error(&err))?;
+        Ok(request.uri().to_owned())
+    })
+}
+
+#[cfg(not(feature = "s3"))]
+fn presign_s3_url(url: &str) -> Result<String, ZcatError> {
+    parse_s3_url(url)?;
+    Err(ZcatError::S3Error(
+        "zcatr was built without the `s3` feature".to_owned(),
+    ))
+}
+
+/// How the requests failing with transient errors are sent again, see `--retries` and
+/// `--retry-delay`.
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct Retries {
+    pub(crate) count: u32,
+    /// The wait before the first retry, doubling at each of the next ones
+    pub(crate) delay: Duration,
+}
+
+/// The waits between the attempts of a request failing with transient errors, doubling
+/// from `--retry-delay` at each of the `--retries` attempts.
+struct Backoff {
+    retries_left: u32,
+    delay: Duration,
+}
+
+impl Backoff {
+    fn new(retries: Retries) -> Backoff {
+        Backoff {
+            retries_left: retries.count,
+            delay: retries.delay,
+        }
+    }
+
+    /// Waits before the next attempt, noting it on the standard error.
+    ///
+    /// # Arguments
+    /// * `name` - The input being downloaded, as given on the command line
+    /// * `err` - The error of the last attempt
+    ///
+    /// # Returns
+    /// `false`, without waiting, if there are no retries left
+    fn wait(&mut self, name: &str, err: &dyn fmt::Display) -> bool {
+        if self.retries_left == 0 {
+            return false;
+        }
+        crate::progress::suspend(|| {
+            eprintln!(
+                "A transient error occurred while downloading the file: {:?}, retrying in {:?}. Error: {}",
+                name, self.delay, err
+            )
+        });
+        std::thread::sleep(self.delay);
+        self.retries_left -= 1;
+        self.delay = self.delay.saturating_mul(2);
+        true
+    }
+}
+
+/// Tells whether a request may succeed if it is sent again: the connection failed or was
+/// interrupted, or the server is overloaded or temporarily unavailable.
+fn is_transient(err: &ureq::Error) -> bool {
+    match err {
+        ureq::Error::StatusCode(status) => *status == 408 || *status == 429 || *status >= 500,
+        ureq::Error::Io(_)
+        | ureq::Error::Timeout(_)
+        | ureq::Error::ConnectionFailed
+        | ureq::Error::BodyStalled => true,
+        _ => false,
+    }
+}
+
+/// Sends a GET request, again after the transient failures (see `is_transient`).
+///
+/// # Arguments
+/// * `name` - The input being downloaded, as given on the command line
+/// * `url` - The URL to request
+/// * `range` - The value of the `Range` header, if any
+/// * `validator` - The value of the `If-Range` header, if any, see `validator`
+/// * `backoff` - The retries left, shared with the caller retrying the reads of the body so
+///   that a download is not retried more than `--retries` times in a row
+fn get_with_retries(
+    name: &str,
+    url: &str,
+    range: Option<&str>,
+    validator: Option<&str>,
+    backoff: &mut Backoff,
+) -> Result<ureq::http::Response<ureq::Body>, ureq::Error> {
+    loop {
+        tracing::debug!(url, range, validator, "sending a GET request");
+        let mut request = ureq::get(url);
+        if let Some(range) = range {
+            request = request.header("Range", range);
+        }
+        if let Some(validator) = validator {
+            request = request.header("If-Range", validator);
+        }
+        match request.call() {
+            Err(err) if is_transient(&err) && backoff.wait(name, &err) => {}
+            result => return result,
+        }
+    }
+}
+
+/// Returns what identifies the version of a file sent by the server, for the range requests
+/// to only be answered with a part of that version (`If-Range`): its `ETag`, or its
+/// `Last-Modified` date when it has no strong `ETag`.
+fn validator(response: &ureq::http::Response<ureq::Body>) -> Option<String> {
+    let header = |name| {
+        response
+            .headers()
+            .get(name)
+            .and_then(|value| value.to_str().ok())
+    };
+    header("etag")
+        .filter(|etag| !etag.starts_with("W/"))
+        .or_else(|| header("last-modified"))
+        .map(str::to_owned)
+}
+
+/// The body of the response to a GET request, read as it is received.
+///
+/// When the connection is interrupted, the download resumes where it stopped with a range
+/// request, if the server accepts them.
+pub(crate) struct Download {
+    /// The input as given on the command line, e.g. before an S3 URL is presigned
+    name: String,
+    url: String,
+    reader: ureq::BodyReader<'static>,
+    /// The number of bytes of the body read so far
+    position: u64,
+    /// The size announced by the server (`Content-Length`), if any
+    len: Option<u64>,
+    /// Whether the server accepts range requests (`Accept-Ranges: bytes`)
+    accepts_ranges: bool,
+    /// The version of the file, sent with the range requests, see `validator`
+    validator: Option<String>,
+    retries: Retries,
+}
+
+impl Download {
+    /// Sends a GET request for a URL, again after transient failures (see `--retries`).
+    ///
+    /// The body is not decoded: compressed files are received as stored on the server.
+    ///
+    /// # Errors
+    /// This function can return:
+    /// * `ZcatError::HttpError` - If the server cannot be reached or answers with an error status
+    /// * `ZcatError::S3Error` - If the request for an S3 object cannot be signed
+    pub(crate) fn start(name: &str, retries: Retries) -> Result<Download, ZcatError> {
+        let url = match is_s3_url(name) {
+            true => presign_s3_url(name)?,
+            false => name.to_owned(),
+        };
+        let response = get_with_retries(name, &url, None, None, &mut Backoff::new(retries))?;
+        let len = response.body().content_length();
+        let accepts_ranges = response
+            .headers()
+            .get("accept-ranges")
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.eq_ignore_ascii_case("bytes"));
+        let validator = validator(&response);
+        Ok(Download {
+            name: name.to_owned(),
+            url,
+            reader: response.into_body().into_reader(),
+            position: 0,
+            len,
+            accepts_ranges,
+            validator,
+            retries,
+        })
+    }
+
+    /// Returns the size announced by the server, if any.
+    pub(crate) fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+    /// Creates a reader fetching the parts of the file it is asked for, when the server
+    /// accepts range requests and announced the size of the file.
+    ///
+    /// # Returns
+    /// The reader, positioned at the start of the file, or `None` if the file cannot be
+    /// read by ranges
+    pub(crate) fn range_reader(&self) -> Option<RangeReader> {
+        let len = self.len.filter(|_| self.accepts_ranges)?;
+        Some(RangeReader {
+            name: self.name.clone(),
+            url: self.url.clone(),
+            len,
+            position: 0,
+            buffer: Vec::new(),
+            buffer_start: 0,
+            range_size: MIN_RANGE_SIZE,
+            validator: self.validator.clone(),
+            retries: self.retries,
+        })
+    }
+}
+
+impl Read for Download {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let mut backoff = Backoff::new(self.retries);
+        loop {
+            let err = match self.reader.read(buf) {
+                // The connection was closed before the end of the body
+                Ok(0) if self.len.is_some_and(|len| self.position < len) && !buf.is_empty() => {
+                    io::Error::from(io::ErrorKind::UnexpectedEof)
+                }
+                Ok(read_bytes) => {
+                    self.position += read_bytes as u64;
+                    return Ok(read_bytes);
+                }
+                Err(err) => err,
+            };
+            if !self.accepts_ranges || !backoff.wait(&self.name, &err) {
+                return Err(err);
+            }
+
+            let range = format!("bytes={}-", self.position);
+            let validator = self.validator.as_deref();
+            let response =
+                get_with_retries(&self.name, &self.url, Some(&range), validator, &mut backoff)
+                    .map_err(io::Error::other)?;
+            // The whole file is sent again when it changed since the download started
+            if response.status() != 206 {
+                return Err(io::Error::other(format!(
+                    "the download could not be resumed ({}), {err}",
+                    response.status()
+                )));
+            }
+            self.reader = response.into_body().into_reader();
+        }
+    }
+}
+
+/// A remote file read with range requests, fetching only the parts which are read.
+///
+/// Each request fetches a range of at least `MIN_RANGE_SIZE` bytes, growing up to
+/// `MAX_RANGE_SIZE` while the file is read sequentially, so that reading the end of a
+/// ZIP archive and then a few of its entries takes a handful of requests.
+pub(crate) struct RangeReader {
+    name: String,
+    url: String,
+    len: u64,
+    position: u64,
+    /// The last range fetched, starting at `buffer_start`
+    buffer: Vec<u8>,
+    buffer_start: u64,
+    range_size: u64,
+    /// The version of the file, see `validator`
+    validator: Option<String>,
+    retries: Retries,
+}
+
+impl RangeReader {
+    /// Returns the size of the remote file.
+    pub(crate) fn len(&self) -> u64 {
+        self.len
+    }
+
+    /// Fetches the range following the current position, again after transient failures.
+    ///
+    /// The range grows while the file is read sequentially and is moved back when it would
+    /// go past the end of the file, as the end of archives is often read backwards.
+    fn fetch(&mut self) -> io::Result<()> {
+        let buffer_end = self.buffer_start + self.buffer.len() as u64;
+        self.range_size = match self.position == buffer_end && !self.buffer.is_empty() {
+            true => (self.range_size * 2).min(MAX_RANGE_SIZE),
+            false => MIN_RANGE_SIZE,
+        };
+        let start = self.position.min(self.len.saturating_sub(self.range_size));
+        let end = (start + self.range_size).min(self.len) - 1;
+
+        let range = format!("bytes={start}-{end}");
+        let mut backoff = Backoff::new(self.retries);
+        loop {
+            let validator = self.validator.as_deref();
+            let response =
+                get_with_retries(&self.name, &self.url, Some(&range), validator, &mut backoff)
+                    .map_err(io::Error::other)?;
+            if response.status() != 206 {
+                return Err(io::Error::other(format!(
+                    "the server did not answer the range request ({})",
+                    response.status()
+                )));
+            }
+            self.buffer.clear();
+            let read = response
+                .into_body()
+                .into_reader()
+                .take(end - start + 1)
+                .read_to_end(&mut self.buffer);
+            let err = match read {
+                Ok(read_bytes) if read_bytes as u64 == end - start + 1 => {
+                    self.buffer_start = start;
+                    return Ok(());
+                }
+                Ok(_) => io::Error::from(io::ErrorKind::UnexpectedEof),
+                Err(err) => err,
+            };
+            if !backoff.wait(&self.name, &err) {
+                self.buffer.clear();
+                return Err(err);
+            }
+        }
+    }
+}
+
+impl Read for RangeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.position >= self.len || buf.is_empty() {
+            return Ok(0);
+        }
+        let buffer_end = self.buffer_start + self.buffer.len() as u64;
+        if self.position < self.buffer_start || self.position >= buffer_end {
+            self.fetch()?;
+        }
+        let offset = (self.position - self.buffer_start) as usize;
+        let available = self.buffer.get(offset..).unwrap_or_default();
+        if available.is_empty() {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        let read_bytes = available.len().min(buf.len());
+        buf[..read_bytes].copy_from_slice(&available[..read_bytes]);
+        self.position += read_bytes as u64;
+        Ok(read_bytes)
+    }
+}
+
+impl Seek for RangeReader {
+    fn seek(&mut self, pos: io::SeekFrom) -> io::Result<u64> {
+        let position = match pos {
+            io::SeekFrom::Start(offset) => Some(offset),
+            io::SeekFrom::End(offset) => self.len.checked_add_signed(offset),
+            io::SeekFrom::Current(offset) => self.position.checked_add_signed(offset),
+        };
+        self.position = position.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative or overflowing position",
+            )
+        })?;
+        Ok(self.position)
+    }
+}
+
+/// A file on an SSH server, given as `user@host:path` like with `scp`.
+#[derive(Debug, PartialEq, Eq)]
+pub(crate) struct SftpLocation<'a> {
+    pub(crate) user: &'a str,
+    pub(crate) host: &'a str,
+    /// The path of the file, relative to the home directory of the user unless absolute
+    pub(crate) path: &'a str,
+}
+
+impl<'a> SftpLocation<'a> {
+    /// Parses a `user@host:path` location.
+    ///
+    /// # Returns
+    /// The location, or `None` if the input is not of this form
+    pub(crate) fn parse(input: &'a str) -> Option<SftpLocation<'a>> {
+        if input.contains("://") {
+            return None;
+        }
+        let (login, path) = input.split_once(':')?;
+        let (user, host) = login.split_once('@')?;
+        let path = path.strip_prefix("~/").unwrap_or(path);
+        // Like with scp, a slash before the colon makes it a local path
+        if user.is_empty() || host.is_empty() || login.contains(['/', '\\']) || path.is_empty() {
+            return None;
+        }
+        Some(SftpLocation { user, host, path })
+    }
+
+    /// Returns the location of an input, unless it is a local file whose name only looks
+    /// like a location.
+    pub(crate) fn of(path: &'a Path) -> Option<SftpLocation<'a>> {
+        SftpLocation::parse(path.to_str()?).filter(|_| !path.exists())
+    }
+}
+
+/// A file read over SFTP.
+#[cfg(feature = "sftp")]
+pub(crate) type SftpFile = ssh2::File;
+
+/// A file read over SFTP, which cannot exist without the `sftp` feature.
+#[cfg(not(feature = "sftp"))]
+pub(crate) enum SftpFile {}
+
+#[cfg(not(feature = "sftp"))]
+impl Read for SftpFile {
+    fn read(&mut self, _buf: &mut [u8]) -> io::Result<usize> {
+        match *self {}
+    }
+}
+
+#[cfg(not(feature = "sftp"))]
+impl Seek for SftpFile {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        match *self {}
+    }
+}
+
+/// Opens a file on an SSH server over SFTP.
+///
+/// The key of the server must be in `~/.ssh/known_hosts`. The user is authenticated with
+/// the SSH agent, then with the default keys of `~/.ssh` which have no passphrase, then with
+/// a password typed in the terminal.
+///
+/// # Returns
+/// * `Ok((SftpFile, Option<u64>))` - The file and its size, if the server tells it
+/// * `Err(ZcatError::SftpError)` - If the server cannot be reached or trusted, the user
+///   cannot be authenticated or the file cannot be opened
+/// * `Err(ZcatError::IoError)` - If the password cannot be read
+#[cfg(feature = "sftp")]
+pub(crate) fn open_sftp(location: &SftpLocation) -> Result<(SftpFile, Option<u64>), ZcatError> {
+    let sftp_error = |err: &dyn std::fmt::Display| {
+        ZcatError::SftpError(format!("{}@{}: {err}", location.user, location.host))
+    };
+    let ssh_dir = std::env::var_os("HOME").map(|home| Path::new(&home).join(".ssh"));
+
+    let mut session = ssh2::Session::new().map_err(|err| sftp_error(&err))?;
+    let stream =
+        std::net::TcpStream::connect((location.host, SSH_PORT)).map_err(|err| sftp_error(&err))?;
+    session.set_tcp_stream(stream);
+    session.handshake().map_err(|err| sftp_error(&err))?;
+
+    let mut known_hosts = session.known_hosts().map_err(|err| sftp_error(&err))?;
+    if let Some(ssh_dir) = &ssh_dir {
+        // A missing file leaves the list empty, and the host unknown
+        let _ = known_hosts.read_file(
+            &ssh_dir.join("known_hosts"),
+            ssh2::KnownHostFileKind::OpenSSH,
+        );
+    }
+    let (host_key, _) = session
+        .host_key()
+        .ok_or_else(|| sftp_error(&"the server sent no host key"))?;
+    match known_hosts.check_port(location.host, SSH_PORT, host_key) {
+        ssh2::CheckResult::Match => {}
+        ssh2::CheckResult::NotFound => {
+            return Err(sftp_error(
+                &"the host key of the server is unknown, connect to it with ssh once to trust it",
+            ))
+        }
+        ssh2::CheckResult::Mismatch => {
+            return Err(sftp_error(
+                &"the host key of the server does not match the one of ~/.ssh/known_hosts",
+            ))
+        }
+        ssh2::CheckResult::Failure => {
+            return Err(sftp_error(
+                &"the host key of the server could not be checked",
+            ))
+        }
+    }
+
+    let _ = session.userauth_agent(location.user);
+    for key in ["id_ed25519", "id_ecdsa", "id_rsa"] {
+        let Some(key) = ssh_dir.as_ref().map(|ssh_dir| ssh_dir.join(key)) else {
+            break;
+        };
+        if !session.authenticated() && key.is_file() {
+            let _ = session.userauth_pubkey_file(location.user, None, &key, None);
+        }
+    }
+    let accepts_password = || {
+        session
+            .auth_methods(location.user)
+            .is_ok_and(|methods| methods.split(',').any(|method| method == "password"))
+    };
+    if !session.authenticated()
+        && std::io::IsTerminal::is_terminal(&io::stdin())
+        && accepts_password()
+    {
+        crate::output::flush();
+        let password = rpassword::prompt_password(format!(
+            "Password of {}@{}: ",
+            location.user, location.host
+        ))?;
+        session
+            .userauth_password(location.user, &password)
+            .map_err(|err| sftp_error(&err))?;
+    }
+    if !session.authenticated() {
+        return Err(sftp_error(&"authentication failed"));
+    }
+
+    let sftp = session.sftp().map_err(|err| sftp_error(&err))?;
+    let path = Path::new(location.path);
+    let file_error = |err: ssh2::Error| sftp_error(&format!("{}: {err}", location.path));
+    let len = sftp.stat(path).map_err(file_error)?.size;
+    let file = sftp.open(path).map_err(file_error)?;
+    Ok((file, len))
+}
+
+#[cfg(not(feature = "sftp"))]
+pub(crate) fn open_sftp(_location: &SftpLocation) -> Result<(SftpFile, Option<u64>), ZcatError> {
+    Err(ZcatError::SftpError(
+        "zcatr was built without the `sftp` feature".to_owned(),
+    ))
+}
@@ -0,0 +1,108 @@
+//! Re-display of the inputs whenever they change, see `--watch`.
+//!
+//! The directories of the inputs are watched rather than the inputs themselves, as builds
+//! often replace their artifacts (e.g. by renaming a temporary file) instead of rewriting
+//! them, which would end the watch of the original file.
+
+use std::{
+    collections::HashSet,
+    io::{self, IsTerminal},
+    path::{Path, PathBuf},
+    sync::{atomic::Ordering, mpsc},
+    time::Duration,
+};
+
+use chrono::Local;
+use notify::{EventKind, RecursiveMode, Watcher};
+
+use crate::{remote, ExitStatus, DECOMPRESSED_BYTES, DISPLAYED_FILES, STDIN_PATH};
+
+/// How long the inputs must stay untouched before they are processed again, so that a file
+/// being written is only processed once it is complete.
+const SETTLE_DELAY: Duration = Duration::from_millis(300);
+
+/// Processes the inputs, then processes them again whenever one of them changes, until zcatr
+/// is interrupted.
+///
+/// # Arguments
+/// * `files` - The inputs
+/// * `process` - A function processing all the inputs, see `process_inputs`
+pub(crate) fn run<F>(files: &[PathBuf], mut process: F) -> !
+where
+    F: FnMut() -> ExitStatus,
+{
+    let mut watched = HashSet::new();
+    for file_path in files {
+        if file_path == Path::new(STDIN_PATH)
+            || remote::is_url(file_path)
+            || remote::SftpLocation::of(file_path).is_some()
+        {
+            eprintln!("--watch only watches local files, not {file_path:?}");
+            ExitStatus::Usage.exit();
+        }
+        watched.insert(absolute(file_path));
+    }
+
+    let (sender, receiver) = mpsc::channel();
+    let watcher = notify::recommended_watcher(sender).and_then(|mut watcher| {
+        let dirs: HashSet<&Path> = watched.iter().filter_map(|path| path.parent()).collect();
+        for dir in dirs {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+        Ok(watcher)
+    });
+    // Dropping the watcher would stop it
+    let _watcher = match watcher {
+        Ok(watcher) => watcher,
+        Err(err) => {
+            eprintln!("The files could not be watched. Error: {err}");
+            ExitStatus::Failure.exit();
+        }
+    };
+
+    let is_terminal = io::stdout().is_terminal();
+    loop {
+        process();
+        crate::output::flush();
+        eprintln!(
+            "Processed at {}, waiting for the files to change (Ctrl-C to stop)",
+            Local::now().format("%H:%M:%S")
+        );
+
+        let changed = |event: &notify::Result<notify::Event>| match event {
+            Ok(event) => {
+                !matches!(event.kind, EventKind::Access(_))
+                    && event.paths.iter().any(|path| watched.contains(path))
+            }
+            Err(_) => false,
+        };
+        loop {
+            match receiver.recv() {
+                Ok(event) if changed(&event) => break,
+                Ok(_) => {}
+                Err(_) => ExitStatus::Failure.exit(),
+            }
+        }
+        while receiver.recv_timeout(SETTLE_DELAY).is_ok() {}
+
+        if is_terminal {
+            // Clears the screen and moves the cursor to its top left corner, like `clear`
+            out!("\x1b[2J\x1b[H");
+        }
+        // Every run starts afresh, e.g. without a separator before the first file
+        DISPLAYED_FILES.store(0, Ordering::Relaxed);
+        DECOMPRESSED_BYTES.store(0, Ordering::Relaxed);
+    }
+}
+
+/// Returns the absolute path of an input, as reported in the events of the watcher.
+fn absolute(file_path: &Path) -> PathBuf {
+    let file_path = std::path::absolute(file_path).unwrap_or_else(|_| file_path.to_owned());
+    // The directory may be reached through symbolic links, the watcher reporting the real path
+    match (file_path.parent(), file_path.file_name()) {
+        (Some(dir), Some(name)) => dir
+            .canonicalize()
+            .map_or_else(|_| file_path.clone(), |dir| dir.join(name)),
+        _ => file_path,
+    }
+}